@@ -11,6 +11,7 @@ const BINDING_HEADER: &str = "
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(clippy::missing_safety_doc)]
+#![allow(clippy::too_many_arguments)]
 
 // Type aliases so the rust side can use glam instead of my implementations.
 type float4=glam::Vec4;
@@ -53,6 +54,8 @@ fn run_bindgen() {
         .blocklist_type("float4")
         .blocklist_type("float4x4")
         .blocklist_type("float3")
+        // Rust has its own (more precise) PI.
+        .blocklist_item("PI")
         .raw_line(BINDING_HEADER)
         .generate()
         .unwrap()
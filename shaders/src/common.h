@@ -1,19 +1,29 @@
 #ifndef common_h
 #define common_h
 
-// Used for the size of the static arrays of intersections.
+// Size of the static arrays of intersections. ShaderInputs::max_hits can lower the limit at runtime.
 #define MAX_HITS 100
 
-// ~log_2(MAX_REFLECT_REFRACT)? or maybe MAX_REFLECT_REFRACT/2
-// Used for avoiding recursion in the colour_at function
-#define MAX_RAY_QUEUE 5
+// Size of the queue used for avoiding recursion in the colour_at function. ShaderInputs::max_ray_queue can lower the limit at runtime.
+#define MAX_RAY_QUEUE 16
 
-// TODO: this is just used for the loop counter so could be passed in at runtime.
-#define MAX_REFLECT_REFRACT 10
+// Size of the explicit stack used to walk the BVH. The tree is built by splitting at the median so this covers billions of shapes.
+#define BVH_STACK_SIZE 32
+
+// Size of the explicit stack used to evaluate nested patterns. The scene loader won't nest them any deeper than this.
+#define PATTERN_STACK_SIZE 16
+
+// Most rays per pixel. More is slow and the rotated grid starts bunching samples together long before this anyway.
+#define MAX_SAMPLES 64
+
+// Most cells along each side of an area light. Each cell is a shadow ray so the scene loader won't go past it.
+#define MAX_LIGHT_STEPS 16
 
 // Used for preventing shadow acne.
 #define EPSILON 0.01
 
+#define PI 3.14159265f
+
 #include "la.h"
 
 // NOT_BUILDING_AS_MSL is set by build.rs when building as c++ for cpu_runner.
@@ -25,6 +35,11 @@
 #define constant
 #define thread
 
+// metal_stdlib has uint32_t built in. la.h declares its own for bindgen.
+#ifndef DOING_RUST_BINDGEN
+#include <cstdint>
+#endif
+
 #else
 
 #include <metal_stdlib>
@@ -32,4 +47,37 @@ using namespace metal;
 
 #endif
 
+// Hash based random numbers (https://www.reedbeta.com/blog/hash-functions-for-gpu-rendering/).
+// There's no global state in a shader so every pixel makes its own from a seed. The same seed always gives the same numbers.
+typedef struct Random {
+    uint32_t state;
+
+    Random(uint32_t seed) : state(seed) {}
+
+    uint32_t next_uint() {
+        state = state * 747796405u + 2891336453u;
+        uint32_t word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+        return (word >> 22u) ^ word;
+    }
+
+    // In [0, 1).
+    float next_float() {
+        return (float) (next_uint() >> 8) / 16777216.0f;
+    }
+} Random;
+
+// Counters written by every pixel at once. On the CPU it's a plain uint32_t so rust can pass an AtomicU32.
+#ifdef NOT_BUILDING_AS_MSL
+typedef uint32_t atomic_uint;
+#ifndef DOING_RUST_BINDGEN
+inline void atomic_increment(device atomic_uint* counter, uint32_t amount) {
+    __atomic_fetch_add(counter, amount, __ATOMIC_RELAXED);
+}
+#endif
+#else
+inline void atomic_increment(device atomic_uint* counter, uint32_t amount) {
+    atomic_fetch_add_explicit(counter, amount, memory_order_relaxed);
+}
+#endif
+
 #endif
@@ -136,7 +136,7 @@ float4x4 transpose(float4x4 m) {
 
 
 float max3(float a, float b, float c) {
-    return fmax(a, fmin(b, c));
+    return fmax(a, fmax(b, c));
 }
 
 float min3(float a, float b, float c) {
@@ -160,3 +160,7 @@ float4 point(float x, float y, float z){
 float4 vector(float x, float y, float z){
     return {x, y, z, 0.0};
 }
+
+float4 cross(float4 a, float4 b){
+    return vector(a.y * b.z - a.z * b.y, a.z * b.x - a.x * b.z, a.x * b.y - a.y * b.x);
+}
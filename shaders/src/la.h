@@ -88,5 +88,7 @@ float3 black();
 float4 zero_vec();
 float4 point(float x, float y, float z);
 float4 vector(float x, float y, float z);
+// Treats them as 3d vectors. Metal only has cross for float3.
+float4 cross(float4 a, float4 b);
 
 #endif
\ No newline at end of file
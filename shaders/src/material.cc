@@ -1,28 +1,117 @@
 #include "material.h"
 
 // https://en.wikipedia.org/wiki/Phong_reflection_model
-float3 Material::lighting(float3 object_colour, PointLight light, float4 position, float4 eye_vector, float4 normal_vector, bool in_shadow) const {
+// Area lights average the diffuse and specular parts over every sample.
+float3 Material::lighting(float3 object_colour, Light light, float4 position, float4 eye_vector, float4 normal_vector, float3 light_visible, thread Random& rng) const {
     float3 base_colour = object_colour * light.intensity;
     float3 ambient_colour = object_colour * ambient;
 
-    if (in_shadow) return ambient_colour;
-
-    float4 light_direction = normalize(light.position - position);
-    float cos_light_to_normal = dot(light_direction, normal_vector);  // Since both are normalized
+    if (max3(light_visible.x, light_visible.y, light_visible.z) <= 0) return ambient_colour;
 
     float3 diffuse_colour = black();
     float3 specular_colour = black();
-    if (cos_light_to_normal >= 0){
-        diffuse_colour = base_colour * diffuse * cos_light_to_normal;
+    for (uint32_t v=0;v<light.vsteps;v++) {
+        for (uint32_t u=0;u<light.usteps;u++) {
+            float4 light_point = light.point_on(u, v, rng);
+            float4 light_direction = light.direction_from(light_point, position);
+            float cos_light_to_normal = dot(light_direction, normal_vector);  // Since both are normalized
+            float falloff = light.spot_falloff(-light_direction) * light.attenuation(light_point, position);
+
+            if (cos_light_to_normal >= 0 && falloff > 0){
+                diffuse_colour += base_colour * diffuse * cos_light_to_normal * falloff;
 
-        float4 reflection_direction = reflect(-light_direction, normal_vector);
-        float cos_reflect_to_eye = dot(reflection_direction, eye_vector);  // Since both are normalized
+                float4 reflection_direction = reflect(-light_direction, normal_vector);
+                float cos_reflect_to_eye = dot(reflection_direction, eye_vector);  // Since both are normalized
 
-        if (cos_reflect_to_eye >= 0){
-            float factor = pow(cos_reflect_to_eye, shininess);
-            specular_colour = light.intensity * specular * factor;
+                if (cos_reflect_to_eye >= 0){
+                    float factor = pow(cos_reflect_to_eye, shininess);
+                    specular_colour += light.intensity * specular * factor * falloff;
+                }
+            }
         }
     }
 
-    return ambient_colour + diffuse_colour + specular_colour;
+    return ambient_colour + (diffuse_colour + specular_colour) * (light_visible / light.samples());
+}
+
+// The colour of light that gets through a transparent material. Glass is usually dark so only the hue matters, not how bright it is.
+float3 Material::shadow_tint() const {
+    float brightest = max3(colour.x, colour.y, colour.z);
+    if (brightest <= 0) return float3(1, 1, 1);
+    return colour / brightest;
+}
+
+// Like % but always positive so the pattern doesn't flip at 0.
+float wrap(float value, float size) {
+    return value - size * floor(value / size);
+}
+
+// Longitude and latitude of a sphere around the origin.
+float2 spherical_map(float4 point) {
+    float theta = atan2(point.x, point.z);
+    float radius = length(point);
+    float phi = acos(point.y / radius);
+    float raw_u = theta / (2 * PI);
+    return float2(1 - (raw_u + 0.5f), 1 - phi / PI);
+}
+
+// Repeats every unit along x and z.
+float2 planar_map(float4 point) {
+    return float2(wrap(point.x, 1), wrap(point.z, 1));
+}
+
+// Wraps around the y axis and repeats every unit up it.
+float2 cylindrical_map(float4 point) {
+    float theta = atan2(point.x, point.z);
+    float raw_u = theta / (2 * PI);
+    return float2(1 - (raw_u + 0.5f), wrap(point.y, 1));
+}
+
+// For a cube from -1 to 1, whichever face the point is closest to.
+CubeFace face_from_point(float4 point) {
+    float coord = max3(abs(point.x), abs(point.y), abs(point.z));
+    if (coord == point.x) return CubeRight;
+    if (coord == -point.x) return CubeLeft;
+    if (coord == point.y) return CubeUp;
+    if (coord == -point.y) return CubeDown;
+    if (coord == point.z) return CubeFront;
+    return CubeBack;
+}
+
+// Each face is unfolded so u goes right and v goes up when looking at it from outside the cube.
+float2 cube_uv(CubeFace face, float4 point) {
+    switch (face) {
+        case CubeLeft:
+            return float2(wrap(point.z + 1, 2) / 2, wrap(point.y + 1, 2) / 2);
+        case CubeFront:
+            return float2(wrap(point.x + 1, 2) / 2, wrap(point.y + 1, 2) / 2);
+        case CubeRight:
+            return float2(wrap(1 - point.z, 2) / 2, wrap(point.y + 1, 2) / 2);
+        case CubeBack:
+            return float2(wrap(1 - point.x, 2) / 2, wrap(point.y + 1, 2) / 2);
+        case CubeUp:
+            return float2(wrap(point.x + 1, 2) / 2, wrap(1 - point.z, 2) / 2);
+        case CubeDown:
+            return float2(wrap(point.x + 1, 2) / 2, wrap(point.z + 1, 2) / 2);
+        default:
+            return float2(0, 0);
+    }
+}
+
+// Like Ring but fades smoothly between a and b.
+float ripples(float4 point) {
+    float dist = sqrt(point.x * point.x + point.z * point.z);
+    return 0.5f - 0.5f * cos(dist * 2 * PI);
+}
+
+// Nearest neighbour. v goes up but the rows go down so it gets flipped.
+float3 texel_colour(Texture texture, const device uint32_t* texels, float2 uv) {
+    // uv should already be in [0, 1] but a bad one (like NaN at the centre of a sphere) can't be allowed to read outside the image.
+    // Converting NaN or a negative to uint32_t is undefined so clamp first. fmax gives the other argument when one is NaN.
+    float u = fmin(fmax(uv.x, 0.0f), 1.0f);
+    float v = fmin(fmax(uv.y, 0.0f), 1.0f);
+    uint32_t x = (uint32_t) round(u * (texture.width - 1));
+    uint32_t y = (uint32_t) round((1 - v) * (texture.height - 1));
+    uint32_t texel = texels[texture.offset + y * texture.width + x];
+    return float3((float) (texel & 0xff), (float) ((texel >> 8) & 0xff), (float) ((texel >> 16) & 0xff)) / 255.0f;
 }
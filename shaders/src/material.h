@@ -2,26 +2,124 @@
 #define material_h
 
 #include "common.h"
+#include "noise.h"
 
-struct PointLight;
+struct Light;
 
 typedef enum PatternType {
     Solid,
     Stripes,
     Gradient,
     Ring,
-    Checker
+    Checker,
+    // Mixes a and b by the same amount everywhere (see Pattern::weight).
+    Blend,
+    // Mix a and b by noise (see noise.h).
+    Marble,
+    Wood,
+    Clouds,
+    // Smooth rings around the y axis. Mostly for bump maps.
+    Ripples,
+    // Turns the point into uv coordinates (see UvMapping) and looks them up in a uv pattern.
+    TextureMap,
+    // The uv patterns. They only make sense inside a TextureMap.
+    UvImage,
+    UvCheckers,
+    // Marks the corners of a face with different colours to check which way round it is.
+    UvAlignCheck
 } PatternType;
 
+// From the book's bonus chapter on texture mapping.
+typedef enum UvMapping {
+    SphericalMap,
+    PlanarMap,
+    CylindricalMap,
+    // A different uv pattern for each face of a cube.
+    CubeMap
+} UvMapping;
+
+// The order of a cube map's uv patterns.
+typedef enum CubeFace {
+    CubeLeft,
+    CubeFront,
+    CubeRight,
+    CubeBack,
+    CubeUp,
+    CubeDown
+} CubeFace;
+
+// The corners of a UvAlignCheck.
+typedef enum UvCorner {
+    UpperLeft,
+    UpperRight,
+    BottomLeft,
+    BottomRight
+} UvCorner;
+
 typedef struct Pattern {
+    // UvAlignCheck uses a as the main colour.
+    // Ignored when a_index or b_index say to use another pattern instead.
     float3 a;
     float3 b;
+    // For a UvAlignCheck, indexed by UvCorner.
+    float3 corners[4];
     PatternType pattern;
+    UvMapping mapping;
+    // For a TextureMap. Cube maps use six in a row starting here, one for each CubeFace.
+    int uv_pattern_index;
+    // For a UvImage.
+    uint32_t texture_index;
+    // How many squares a UvCheckers has across u and v.
+    float width;
+    float height;
+    // Index of the pattern to use in place of a or b. -1 to use the colour.
+    int a_index;
+    int b_index;
+    // How much of b a Blend mixes in.
+    float weight;
+    // How far noise moves the point before the pattern is evaluated. 0 leaves it alone.
+    // Marble and Wood bend their veins and rings by this much instead.
+    float perturb;
+    // About how far apart the bumps in the noise are.
+    float noise_scale;
+    // How many layers of finer detail the noise has.
+    uint32_t octaves;
     float4x4 transform_inverse;
+
+    float b_amount(float4 pattern_point) const;
 } Pattern;
 
+// An image in the texels buffer. Each texel is a packed colour with red in the lowest byte.
+// Rows go from the top of the image down.
+typedef struct Texture {
+    uint32_t offset;
+    uint32_t width;
+    uint32_t height;
+} Texture;
+
+// Each of these gives u and v in [0, 1].
+float2 spherical_map(float4 point);
+float2 planar_map(float4 point);
+float2 cylindrical_map(float4 point);
+CubeFace face_from_point(float4 point);
+float2 cube_uv(CubeFace face, float4 point);
+
+float3 texel_colour(Texture texture, const device uint32_t* texels, float2 uv);
+
+float ripples(float4 point);
+
+// How a material's bump pattern bends the normal (see WorldView::bumped_normal).
+typedef enum BumpMapping {
+    // The brightness of the pattern is how far the surface is raised.
+    HeightMap,
+    // The colour of the pattern is the normal relative to the surface (red along u, green along v, blue straight out).
+    NormalMap
+} BumpMapping;
+
 typedef struct Material {
     float3 colour;
+    // Light given off by the surface itself. Added no matter what lights or shadows there are.
+    float3 emissive;
     int pattern_index;
     float ambient;
     float diffuse;
@@ -30,8 +128,15 @@ typedef struct Material {
     float reflective;
     float transparency;
     float refractive_index;
+    // Index of the pattern that bends the normal. -1 to use the shape's normal as it is.
+    int bump_index;
+    BumpMapping bump;
+    // How tall a HeightMap's bumps are. Scales how far a NormalMap tilts the normal.
+    float bump_amount;
     
-    float3 lighting(float3 object_colour, PointLight light, float4 position, float4 eye_vector, float4 normal_vector, bool in_shadow) const;
+    // light_visible is how much of the light isn't in shadow, per channel (see WorldView::intensity_at).
+    float3 lighting(float3 object_colour, Light light, float4 position, float4 eye_vector, float4 normal_vector, float3 light_visible, thread Random& rng) const;
+    float3 shadow_tint() const;
 } Material;
 
 #include "world.h"
@@ -0,0 +1,109 @@
+#include "noise.h"
+
+// A random number for each corner of the unit grid. Mixed the same way as Random so neighbouring corners aren't related.
+uint32_t hash_corner(int x, int y, int z) {
+    Random rng(((uint32_t) x) * 73856093u ^ ((uint32_t) y) * 19349663u ^ ((uint32_t) z) * 83492791u);
+    return rng.next_uint();
+}
+
+// Dot product of (x, y, z) with one of the 12 vectors from the middle of a cube to the middle of its edges.
+// The hash picks which one. 16 cases so it's just a bit mask, with 4 of the vectors used twice.
+float gradient_dot(uint32_t hash, float x, float y, float z) {
+    uint32_t h = hash & 15;
+    float u = h < 8 ? x : y;
+    float v = h < 4 ? y : (h == 12 || h == 14 ? x : z);
+    return ((h & 1) == 0 ? u : -u) + ((h & 2) == 0 ? v : -v);
+}
+
+// 6t^5 - 15t^4 + 10t^3. Flat at both ends so there are no creases along the grid lines.
+float fade(float t) {
+    return t * t * t * (t * (t * 6 - 15) + 10);
+}
+
+float interpolate(float a, float b, float t) {
+    return a + t * (b - a);
+}
+
+float perlin_noise(float4 point) {
+    float fx = floor(point.x);
+    float fy = floor(point.y);
+    float fz = floor(point.z);
+    int x = (int) fx;
+    int y = (int) fy;
+    int z = (int) fz;
+    // Where the point is inside its cell.
+    float dx = point.x - fx;
+    float dy = point.y - fy;
+    float dz = point.z - fz;
+
+    float c000 = gradient_dot(hash_corner(x, y, z), dx, dy, dz);
+    float c100 = gradient_dot(hash_corner(x + 1, y, z), dx - 1, dy, dz);
+    float c010 = gradient_dot(hash_corner(x, y + 1, z), dx, dy - 1, dz);
+    float c110 = gradient_dot(hash_corner(x + 1, y + 1, z), dx - 1, dy - 1, dz);
+    float c001 = gradient_dot(hash_corner(x, y, z + 1), dx, dy, dz - 1);
+    float c101 = gradient_dot(hash_corner(x + 1, y, z + 1), dx - 1, dy, dz - 1);
+    float c011 = gradient_dot(hash_corner(x, y + 1, z + 1), dx, dy - 1, dz - 1);
+    float c111 = gradient_dot(hash_corner(x + 1, y + 1, z + 1), dx - 1, dy - 1, dz - 1);
+
+    float u = fade(dx);
+    float v = fade(dy);
+    float w = fade(dz);
+    return interpolate(
+        interpolate(interpolate(c000, c100, u), interpolate(c010, c110, u), v),
+        interpolate(interpolate(c001, c101, u), interpolate(c011, c111, u), v),
+        w
+    );
+}
+
+// Divided by the total amplitude so adding octaves adds detail without making it any stronger.
+float fbm(float4 point, uint32_t octaves) {
+    float total = 0;
+    float amplitude = 1;
+    float max_total = 0;
+    for (uint32_t i=0;i<octaves;i++) {
+        total += perlin_noise(point) * amplitude;
+        max_total += amplitude;
+        amplitude *= 0.5f;
+        point = point * 2;
+    }
+    return total / max_total;
+}
+
+float turbulence(float4 point, uint32_t octaves) {
+    float total = 0;
+    float amplitude = 1;
+    float max_total = 0;
+    for (uint32_t i=0;i<octaves;i++) {
+        total += abs(perlin_noise(point)) * amplitude;
+        max_total += amplitude;
+        amplitude *= 0.5f;
+        point = point * 2;
+    }
+    return total / max_total;
+}
+
+// Each direction samples the noise somewhere far away from the others so they move independently.
+float4 perturb_point(float4 point, float amount, float scale, uint32_t octaves) {
+    float4 p = point / scale;
+    float dx = fbm(p, octaves);
+    float dy = fbm(p + vector(31.4f, 15.9f, 26.5f), octaves);
+    float dz = fbm(p + vector(-35.8f, 97.9f, -32.3f), octaves);
+    return point + vector(dx, dy, dz) * amount;
+}
+
+// Smooth bands along x (a at even numbers, b at odd numbers) bent into veins by the turbulence.
+float marble(float4 point, float amount, float scale, uint32_t octaves) {
+    float x = point.x + amount * turbulence(point / scale, octaves);
+    return 0.5f - 0.5f * cos(x * PI);
+}
+
+// Rings around the y axis that fade from a to b across each one, wobbled by the noise so they aren't perfect circles.
+float wood(float4 point, float amount, float scale, uint32_t octaves) {
+    float dist = sqrt(point.x * point.x + point.z * point.z) + amount * fbm(point / scale, octaves);
+    return dist - floor(dist);
+}
+
+// Fbm is mostly within [-0.5, 0.5] so this uses the whole range between a and b.
+float clouds(float4 point, float scale, uint32_t octaves) {
+    return fmin(fmax(0.5f + fbm(point / scale, octaves), 0.0f), 1.0f);
+}
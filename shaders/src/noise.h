@@ -0,0 +1,27 @@
+#ifndef noise_h
+#define noise_h
+
+#include "common.h"
+
+// Smooth random values that are the same every time for the same point.
+// They all use the point's x, y and z and ignore w.
+
+// Ken Perlin's improved noise (https://mrl.cs.nyu.edu/~perlin/noise/) with hashed gradients instead of a permutation table.
+// Roughly in [-1, 1] and exactly 0 at whole numbers.
+float perlin_noise(float4 point);
+
+// Fractal Brownian motion. Adds octaves of noise that are each twice as detailed and half as strong as the last.
+float fbm(float4 point, uint32_t octaves);
+
+// Like fbm but adds up the absolute value of each octave, which gives sharp creases where the noise crosses 0. In [0, 1].
+float turbulence(float4 point, uint32_t octaves);
+
+// Moves the point by up to amount in each direction. Features of the noise are about scale apart.
+float4 perturb_point(float4 point, float amount, float scale, uint32_t octaves);
+
+// How much of b the noise patterns use at a point (see Pattern::b_amount).
+float marble(float4 point, float amount, float scale, uint32_t octaves);
+float wood(float4 point, float amount, float scale, uint32_t octaves);
+float clouds(float4 point, float scale, uint32_t octaves);
+
+#endif
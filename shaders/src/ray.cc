@@ -1,12 +1,16 @@
 #include "ray.h"
 
-Ray Camera::ray_for_pixel(float x, float y) const constant {
+Ray Camera::ray_for_pixel(float x, float y) const {
     // We want the middle of the pixel.
+    return ray_through(x + 0.5f, y + 0.5f);
+}
+
+Ray Camera::ray_through(float x, float y) const {
     // Adjusted from canvas space to world space units.
     // Since the camera is at (0, 0), translate. This flips it so high y becomes negative.
     // But canvas units are kinda flipped too, so it cancels out? And canvas looks at -x so x flip works too.
-    float object_x = half_width - (((x + 0.5f)) * pixel_size);
-    float object_y = half_height - (((y + 0.5f)) * pixel_size);
+    float object_x = half_width - (x * pixel_size);
+    float object_y = half_height - (y * pixel_size);
 
     // Position of the pixel in the camera's object space.
     float4 pixel_object_point = point(object_x, object_y, -1);
@@ -18,6 +22,42 @@ Ray Camera::ray_for_pixel(float x, float y) const constant {
     return Ray(camera_world_point, ray_direction);
 };
 
+float2 sample_offset(SamplePattern pattern, uint32_t i, uint32_t n, thread Random& rng) {
+    // As square as possible. If n isn't a square number, the last row isn't full.
+    uint32_t columns = (uint32_t) ceil(sqrt((float) n));
+    uint32_t rows = (n + columns - 1) / columns;
+    float x = ((i % columns) + 0.5f) / columns;
+    float y = ((i / columns) + 0.5f) / rows;
+
+    switch (pattern) {
+        case SampleGrid:
+            return float2(x, y);
+        case SampleRotatedGrid: {
+            // Turn around the middle of the pixel. Big grids poke out the corners so wrap them back in.
+            float c = 0.894427f;  // cos(atan(1/2))
+            float s = 0.447214f;  // sin(atan(1/2))
+            float rx = 0.5f + (x - 0.5f) * c - (y - 0.5f) * s;
+            float ry = 0.5f + (x - 0.5f) * s + (y - 0.5f) * c;
+            return float2(rx - floor(rx), ry - floor(ry));
+        }
+        case SampleJittered:
+            return float2(x + (rng.next_float() - 0.5f) / columns, y + (rng.next_float() - 0.5f) / rows);
+        default:
+            return float2(0.5f, 0.5f);
+    }
+}
+
+// Picks a point on a disk and projects it up onto a hemisphere (Malley's method).
+float4 cosine_direction(float4 normal, thread Random& rng) {
+    float angle = 2 * PI * rng.next_float();
+    float r2 = rng.next_float();
+    float r = sqrt(r2);
+    // Any two vectors perpendicular to the normal and each other.
+    float4 helper = abs(normal.x) > 0.9f ? vector(0, 1, 0) : vector(1, 0, 0);
+    float4 tangent = normalize(cross(helper, normal));
+    float4 bitangent = cross(normal, tangent);
+    return normalize(tangent * (r * cos(angle)) + bitangent * (r * sin(angle)) + normal * sqrt(1 - r2));
+}
 
 Ray Ray::transform(float4x4 mat) const {
     return Ray(mat * origin, mat * direction);
@@ -31,12 +71,8 @@ float4 Ray::position(float t) const {
 //       seems like you mostly don't need the whole thing sorted
 //       except for refraction so doing it for shadows too is strange,
 //       should just look for one in the right range without swapping.
-void Intersections::add(float t, uint32_t shape_index) {
-    Intersection hit = {t, shape_index};
-    if (hit.t >= 0) {
-        is_hit = true;
-    }
-
+void Intersections::add(float t, uint32_t shape_index, float u, float v) {
+    Intersection hit = {t, shape_index, u, v};
     for (int i=0;i<count;i++) {
         if (hit.t < hits[i].t) {
             Intersection temp = hits[i];
@@ -45,8 +81,14 @@ void Intersections::add(float t, uint32_t shape_index) {
         }
     }
 
-    hits[count] = hit;
-    count += 1;
+    // Now hit is the furthest one so that's the one to drop when full.
+    if (count < limit) {
+        hits[count] = hit;
+        count += 1;
+    }
+
+    // It's sorted so there's a hit in front of the ray if the furthest one is.
+    is_hit = count > 0 && hits[count - 1].t >= 0;
 }
 
 
@@ -62,9 +104,9 @@ Intersection Intersections::get_hit() const {
 };
 
 
-int Intersections::index_of(const thread Intersection& hit) const {
+int Intersections::index_of(uint32_t shape_index) const {
     for (int i=0;i<count;i++) {
-        if (hits[i] == hit) return i;
+        if (hits[i].obj == shape_index) return i;
     }
     return -1;
 }
@@ -76,3 +118,28 @@ void Intersections::remove(int index) {
     }
     count--;
 }
+
+
+// Removes the hits on a CSG's children that aren't on the surface of the combined shape.
+// Hits on shapes outside the CSG are left alone and it's done in place so the list stays sorted.
+void Intersections::filter_csg(const thread CsgNode& node) {
+    bool in_left = false;
+    bool in_right = false;
+    int kept = 0;
+    is_hit = false;
+    for (int i=0;i<count;i++) {
+        Intersection hit = hits[i];
+        bool is_left = hit.obj >= node.first_left && hit.obj < node.first_right;
+        bool is_right = hit.obj >= node.first_right && hit.obj < node.end;
+
+        if ((!is_left && !is_right) || node.allows(is_left, in_left, in_right)) {
+            hits[kept] = hit;
+            kept++;
+            if (hit.t >= 0) is_hit = true;
+        }
+
+        if (is_left) in_left = !in_left;
+        if (is_right) in_right = !in_right;
+    }
+    count = kept;
+}
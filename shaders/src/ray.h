@@ -4,6 +4,8 @@
 #include "common.h"
 
 struct Shape;
+struct TriangleData;
+struct CsgNode;
 
 typedef struct Ray {
     float4 origin;
@@ -16,6 +18,16 @@ typedef struct Ray {
 } Ray;
 
 
+// Where the rays for one pixel go when there's more than one (ShaderInputs::samples).
+typedef enum SamplePattern {
+    // Evenly spaced rows and columns.
+    SampleGrid,
+    // The grid turned by atan(1/2) so no two samples line up. Better for edges that are nearly horizontal or vertical.
+    SampleRotatedGrid,
+    // One random point in each grid cell.
+    SampleJittered
+} SamplePattern;
+
 typedef struct Camera {
     float4x4 transform_inverse;
     float pixel_size;
@@ -25,12 +37,24 @@ typedef struct Camera {
     float vsize;
     float field_of_view;
     
-    Ray ray_for_pixel(float x, float y) const constant;
+    // Through the middle of the pixel.
+    Ray ray_for_pixel(float x, float y) const;
+    // Through any point on the canvas. (x, y) is the top left corner of pixel (x, y).
+    Ray ray_through(float x, float y) const;
 } Camera;
 
+// Where sample i of n goes within a pixel. Both parts are in [0, 1).
+float2 sample_offset(SamplePattern pattern, uint32_t i, uint32_t n, thread Random& rng);
+
+// A random direction on the side of the surface the normal points to. More likely near the normal (by the cosine of the angle to it).
+float4 cosine_direction(float4 normal, thread Random& rng);
+
 typedef struct Intersection {
     float t;
     uint32_t obj;
+    // Where the ray hit a triangle. Smooth triangles use it to interpolate the normal.
+    float u;
+    float v;
     
     bool operator==(const thread Intersection& rhs) const {
         return t == rhs.t && obj == rhs.obj;
@@ -39,14 +63,17 @@ typedef struct Intersection {
 
 typedef struct Intersections {
     int count;
+    // Hits past this are dropped (the furthest first). At most MAX_HITS.
+    int limit;
     bool is_hit;
     Intersection hits[MAX_HITS];
     
-    Intersections(){
+    Intersections() : Intersections(MAX_HITS) {}
+    Intersections(int l) : limit(l) {
         clear();
     }
     Intersection get_hit() const;
-    void add(float t, uint32_t shape_index);
+    void add(float t, uint32_t shape_index, float u = 0, float v = 0);
     bool has_hit() const {
         return is_hit;
     };
@@ -60,8 +87,10 @@ typedef struct Intersections {
     const thread Intersection& last() const {
         return hits[count - 1];
     }
-    int index_of(const thread Intersection& hit) const;
+    // The first hit on that shape or -1.
+    int index_of(uint32_t shape_index) const;
     void remove(int i);
+    void filter_csg(const thread CsgNode& node);
 } Intersections;
 
 typedef struct RayInfo {
@@ -76,11 +105,12 @@ typedef struct RayQueue {
     RayInfo rays[MAX_RAY_QUEUE];
     int start;
     int end;
+    // At most MAX_RAY_QUEUE.
+    int limit;
+    // Rays that didn't fit. Added to WorldView::dropped_rays so the budget can be checked from rust.
+    uint32_t dropped;
     
-    RayQueue(){
-        start = 0;
-        end = 0;
-    }
+    RayQueue(int l) : start(0), end(0), limit(l), dropped(0) {}
     
     RayInfo pop() {
         int index = start % MAX_RAY_QUEUE;
@@ -89,10 +119,12 @@ typedef struct RayQueue {
     }
     
     void push(Ray r, float weight) {
-        // TODO: bounds checking seems very cringe but also i dont want it to stomp on stuff.
-        //       currently drops later ones instead of earlier ones which seems better but maybe not worth it.
+        // Drops later ones instead of earlier ones which seems better but maybe not worth it.
         int count = end - start;
-        if (count > MAX_RAY_QUEUE) return;
+        if (count >= limit) {
+            dropped++;
+            return;
+        }
         int index = end % MAX_RAY_QUEUE;
         rays[index] = RayInfo(r, weight);
         end++;
@@ -14,12 +14,18 @@ fragment float4 trace_pixel(
     VertOut in [[stage_in]],
     constant ShaderInputs& inputs [[buffer(0)]],
     const device Shape* shapes [[buffer(1)]],
-    const device PointLight* lights [[buffer(2)]],
-    const device Pattern* patterns [[buffer(3)]]
+    const device Light* lights [[buffer(2)]],
+    const device Pattern* patterns [[buffer(3)]],
+    const device TriangleData* triangles [[buffer(4)]],
+    const device BvhNode* bvh [[buffer(5)]],
+    const device CsgNode* csgs [[buffer(6)]],
+    device atomic_uint* dropped_rays [[buffer(7)]],
+    const device Texture* textures [[buffer(8)]],
+    const device uint32_t* texels [[buffer(9)]]
 ){
-    WorldView world = WorldView(shapes, lights, inputs, patterns);
-    Ray ray = inputs.camera.ray_for_pixel(in.position.x, in.position.y);
-    return float4(world.colour_at(ray), 1.0);
+    WorldView world = WorldView(shapes, lights, inputs, patterns, triangles, bvh, csgs, dropped_rays, textures, texels);
+    // The position is the middle of the pixel but pixel_colour wants the corner.
+    return float4(world.pixel_colour(floor(in.position.x), floor(in.position.y)), 1.0);
 };
 
 // Big triangle that covers the screen so the fragment shader runs for every pixel.
@@ -1,7 +1,7 @@
 #include "shapes.h"
 #include "common.h"
 
-float4 Shape::normal_at(float4 world_pos) const {
+float4 Shape::normal_at(float4 world_pos, const thread Intersection& hit, const device TriangleData* triangles) const {
     float4 object_space_point = transform_inverse * world_pos;
     float4 object_space_normal;
     
@@ -25,14 +25,56 @@ float4 Shape::normal_at(float4 world_pos) const {
             }
             break;
         }
+        case Cylinder: {
+            float dist = object_space_point.x * object_space_point.x + object_space_point.z * object_space_point.z;
+            if (dist < 1 && object_space_point.y >= maximum - EPSILON) {
+                object_space_normal = vector(0, 1, 0);
+            } else if (dist < 1 && object_space_point.y <= minimum + EPSILON) {
+                object_space_normal = vector(0, -1, 0);
+            } else {
+                object_space_normal = vector(object_space_point.x, 0, object_space_point.z);
+            }
+            break;
+        }
+        case Cone: {
+            float dist = object_space_point.x * object_space_point.x + object_space_point.z * object_space_point.z;
+            if (dist < maximum * maximum && object_space_point.y >= maximum - EPSILON) {
+                object_space_normal = vector(0, 1, 0);
+            } else if (dist < minimum * minimum && object_space_point.y <= minimum + EPSILON) {
+                object_space_normal = vector(0, -1, 0);
+            } else {
+                float y = sqrt(dist);
+                if (object_space_point.y > 0) y = -y;
+                object_space_normal = vector(object_space_point.x, y, object_space_point.z);
+            }
+            break;
+        }
+        case Triangle: {
+            object_space_normal = triangles[triangle_index].normal;
+            break;
+        }
+        case SmoothTriangle: {
+            TriangleData tri = triangles[triangle_index];
+            object_space_normal = tri.n2 * hit.u + tri.n3 * hit.v + tri.n1 * (1 - hit.u - hit.v);
+            break;
+        }
+        case Csg: {
+            // Never happens. Hits are always on one of the children.
+            object_space_normal = vector(0, 1, 0);
+            break;
+        }
     }
     
+    return normal_to_world(object_space_normal);
+}
+
+float4 Shape::normal_to_world(float4 object_space_normal) const {
     float4 world_space_normal = transpose(transform_inverse) * object_space_normal;
     world_space_normal.w = 0;
     return normalize(world_space_normal);
 }
 
-void Shape::intersect(const thread Ray& world_ray, thread Intersections& hits) const {
+void Shape::intersect(const thread Ray& world_ray, thread Intersections& hits, const device TriangleData* triangles) const {
     Ray object_space_ray = world_ray.transform(transform_inverse);
     // Look at me. Look at me. I am the VTable now.
     switch (shape) {
@@ -45,7 +87,35 @@ void Shape::intersect(const thread Ray& world_ray, thread Intersections& hits) c
         case Cube: {
             return local_intersect_cube(object_space_ray, hits);
         }
+        case Cylinder: {
+            return local_intersect_cylinder(object_space_ray, hits);
+        }
+        case Cone: {
+            return local_intersect_cone(object_space_ray, hits);
+        }
+        case Triangle:
+        case SmoothTriangle: {
+            return local_intersect_triangle(object_space_ray, hits, triangles[triangle_index]);
+        }
+        case Csg: {
+            // Needs the other shapes so WorldView::intersect_csg does it.
+            return;
+        }
+    }
+}
+
+// Decides if a hit on one side of the CSG is on the surface of the combined shape.
+// The in_ flags say if the ray was inside each side just before this hit.
+bool CsgNode::allows(bool hit_left, bool in_left, bool in_right) const {
+    switch (operation) {
+        case CsgUnion:
+            return (hit_left && !in_right) || (!hit_left && !in_left);
+        case CsgIntersection:
+            return (hit_left && in_right) || (!hit_left && in_left);
+        case CsgDifference:
+            return (hit_left && !in_right) || (!hit_left && in_left);
     }
+    return false;
 }
 
 void Shape::local_intersect_sphere(const thread Ray& ray, thread Intersections& hits) const {
@@ -105,3 +175,93 @@ void Shape::local_intersect_cube(const thread Ray& ray, thread Intersections& hi
         hits.add(tmax, index);
     }
 }
+
+void Shape::local_intersect_cylinder(const thread Ray& ray, thread Intersections& hits) const {
+    float a = ray.direction.x * ray.direction.x + ray.direction.z * ray.direction.z;
+
+    // Parallel to the y-axis can only hit the caps.
+    if (abs(a) > 0) {
+        float b = 2 * ray.origin.x * ray.direction.x + 2 * ray.origin.z * ray.direction.z;
+        float c = ray.origin.x * ray.origin.x + ray.origin.z * ray.origin.z - 1;
+        float discriminant = (b * b) - (4 * a * c);
+        if (discriminant < 0) return;
+
+        // a is positive so these are already in order.
+        float d = sqrt(discriminant);
+        float t0 = (-b - d) / (2 * a);
+        float t1 = (-b + d) / (2 * a);
+
+        float y0 = ray.origin.y + t0 * ray.direction.y;
+        if (minimum < y0 && y0 < maximum) hits.add(t0, index);
+        float y1 = ray.origin.y + t1 * ray.direction.y;
+        if (minimum < y1 && y1 < maximum) hits.add(t1, index);
+    }
+
+    intersect_caps(ray, hits, 1, 1);
+}
+
+// Double-napped: two cones meeting at their tips at the origin.
+void Shape::local_intersect_cone(const thread Ray& ray, thread Intersections& hits) const {
+    float a = ray.direction.x * ray.direction.x - ray.direction.y * ray.direction.y + ray.direction.z * ray.direction.z;
+    float b = 2 * ray.origin.x * ray.direction.x - 2 * ray.origin.y * ray.direction.y + 2 * ray.origin.z * ray.direction.z;
+    float c = ray.origin.x * ray.origin.x - ray.origin.y * ray.origin.y + ray.origin.z * ray.origin.z;
+
+    if (abs(a) > 0) {
+        // Rays that graze the surface can end up slightly negative from rounding.
+        float discriminant = (b * b) - (4 * a * c);
+        if (discriminant > -EPSILON) {
+            float d = sqrt(fmax(discriminant, 0.0f));
+            float t0 = (-b - d) / (2 * a);
+            float t1 = (-b + d) / (2 * a);
+
+            float y0 = ray.origin.y + t0 * ray.direction.y;
+            if (minimum < y0 && y0 < maximum) hits.add(t0, index);
+            float y1 = ray.origin.y + t1 * ray.direction.y;
+            if (minimum < y1 && y1 < maximum) hits.add(t1, index);
+        }
+    } else if (abs(b) > 0) {
+        // Parallel to one of the halves so it only hits the other once.
+        float t = -c / (2 * b);
+        float y = ray.origin.y + t * ray.direction.y;
+        if (minimum < y && y < maximum) hits.add(t, index);
+    }
+
+    intersect_caps(ray, hits, abs(minimum), abs(maximum));
+}
+
+bool check_cap(const thread Ray& ray, float t, float radius) {
+    float x = ray.origin.x + t * ray.direction.x;
+    float z = ray.origin.z + t * ray.direction.z;
+    // Tolerance so rays through the rim don't slip between the cap and the side.
+    return (x * x + z * z) <= radius * radius + EPSILON;
+}
+
+void Shape::intersect_caps(const thread Ray& ray, thread Intersections& hits, float min_radius, float max_radius) const {
+    if (!closed || abs(ray.direction.y) == 0) return;
+
+    float t = (minimum - ray.origin.y) / ray.direction.y;
+    if (check_cap(ray, t, min_radius)) hits.add(t, index);
+
+    t = (maximum - ray.origin.y) / ray.direction.y;
+    if (check_cap(ray, t, max_radius)) hits.add(t, index);
+}
+
+// https://en.wikipedia.org/wiki/M%C3%B6ller%E2%80%93Trumbore_intersection_algorithm
+void Shape::local_intersect_triangle(const thread Ray& ray, thread Intersections& hits, const device TriangleData& tri) const {
+    float4 dir_cross_e2 = cross(ray.direction, tri.e2);
+    float det = dot(tri.e1, dir_cross_e2);
+    // Not EPSILON because that's way bigger than the determinant for the tiny triangles in a detailed mesh.
+    if (abs(det) < 0.0000001f) return;  // parallel
+
+    float f = 1.0f / det;
+    float4 p1_to_origin = ray.origin - tri.p1;
+    float u = f * dot(p1_to_origin, dir_cross_e2);
+    if (u < 0 || u > 1) return;
+
+    float4 origin_cross_e1 = cross(p1_to_origin, tri.e1);
+    float v = f * dot(ray.direction, origin_cross_e1);
+    if (v < 0 || (u + v) > 1) return;
+
+    float t = f * dot(tri.e2, origin_cross_e1);
+    hits.add(t, index, u, v);
+}
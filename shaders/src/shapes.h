@@ -8,20 +8,76 @@
 typedef enum ShapeType {
     Sphere,
     Plane,
-    Cube
+    Cube,
+    Cylinder,
+    Cone,
+    Triangle,
+    SmoothTriangle,
+    Csg
 } ShapeType;
 
+// Prefixed because unscoped enums share the global namespace (and there's already an Intersection).
+typedef enum CsgOperation {
+    CsgUnion,
+    CsgIntersection,
+    CsgDifference
+} CsgOperation;
+
+// The children of a CSG are stored next to each other in the shapes buffer:
+// [first_left, first_right) is the left side and [first_right, end) is the right.
+// Nested CSGs come before their parents so [first_node, this node] is the whole tree and filtering them in order works from the bottom up.
+typedef struct CsgNode {
+    CsgOperation operation;
+    uint32_t first_node;
+    uint32_t first_left;
+    uint32_t first_right;
+    uint32_t end;
+
+    bool allows(bool hit_left, bool in_left, bool in_right) const;
+} CsgNode;
+
+// Vertex data for triangles lives in its own buffer so meshes don't make every Shape bigger.
+typedef struct TriangleData {
+    float4 p1;
+    float4 p2;
+    float4 p3;
+    float4 e1;
+    float4 e2;
+    float4 normal;
+    // Only used by SmoothTriangle.
+    float4 n1;
+    float4 n2;
+    float4 n3;
+} TriangleData;
+
 typedef struct Shape {
     float4x4 transform_inverse;
     ShapeType shape;
     uint32_t index;
     Material material;
+    // Cylinders and cones are truncated to this range on the y-axis in object space.
+    float minimum;
+    float maximum;
+    bool closed;
+    // When false, light goes straight through it. Good for backdrops or the fixture around a light.
+    bool casts_shadow;
+    // When false, it's lit as if nothing was between it and the lights.
+    bool receives_shadow;
+    // Only used by triangles. Index into the buffer of TriangleData.
+    uint32_t triangle_index;
+    // Only used by Csg. Index into the buffer of CsgNode of the root of the tree.
+    uint32_t csg_index;
 
-    float4 normal_at(float4 world_pos) const;
-    void intersect(const thread Ray& world_ray, thread Intersections& hits) const;
+    float4 normal_at(float4 world_pos, const thread Intersection& hit, const device TriangleData* triangles) const;
+    float4 normal_to_world(float4 object_space_normal) const;
+    void intersect(const thread Ray& world_ray, thread Intersections& hits, const device TriangleData* triangles) const;
     void local_intersect_sphere(const thread Ray& object_ray, thread Intersections& hits) const;
     void local_intersect_plane(const thread Ray& object_ray, thread Intersections& hits) const;
     void local_intersect_cube(const thread Ray& object_ray, thread Intersections& hits) const;
+    void local_intersect_cylinder(const thread Ray& object_ray, thread Intersections& hits) const;
+    void local_intersect_cone(const thread Ray& object_ray, thread Intersections& hits) const;
+    void intersect_caps(const thread Ray& object_ray, thread Intersections& hits, float min_radius, float max_radius) const;
+    void local_intersect_triangle(const thread Ray& object_ray, thread Intersections& hits, const device TriangleData& tri) const;
 } Shape;
 
 #endif
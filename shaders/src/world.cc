@@ -1,77 +1,249 @@
 #include "world.h"
 
+// (x, y) is the top left corner of the pixel. Averages the colour of inputs.samples rays spread across it.
+float3 WorldView::pixel_colour(float x, float y) const {
+    // Seeded by the pixel and frame so jittered samples (and lights) don't flicker when nothing moves unless the frame changes.
+    Random rng(((uint32_t) x) * 73856093u ^ ((uint32_t) y) * 19349663u ^ inputs.frame_index * 83492791u);
+    float3 colour = black();
+    for (uint32_t i=0;i<inputs.samples;i++) {
+        float2 offset = sample_offset(inputs.sample_pattern, i, inputs.samples, rng);
+        Ray ray = inputs.camera.ray_through(x + offset.x, y + offset.y);
+        colour += inputs.integrator == PathTracing ? path_colour(ray, rng) : colour_at(ray, rng);
+    }
+    return colour / (float) inputs.samples;
+}
+
 // Since Metal doesn't allow recursion in fragment shaders, this iteratively processes a queue of rays.
 // When a new ray needs to be spawned for a reflection or refraction, it just gets pushed to the queue.
-float3 WorldView::colour_at(const thread Ray& first_ray) const {
+float3 WorldView::colour_at(const thread Ray& first_ray, thread Random& rng) const {
     float3 colour = black();
-    Intersections hits;
-    RayQueue queue;
+    Intersections hits(inputs.max_hits);
+    RayQueue queue(inputs.max_ray_queue);
     queue.push(first_ray, 1.0);
-    for (int i=0;i<MAX_REFLECT_REFRACT && !queue.is_empty();i++) {
+    for (uint32_t i=0;i<inputs.max_reflect_refract && !queue.is_empty();i++) {
         RayInfo ray = queue.pop();
+        hits.clear();
         intersect(ray.ray, hits);
 
         if (hits.has_hit()) {
             Comps comps = prepare_comps(hits.get_hit(), ray.ray, hits);
-            colour += shade_hit(comps) * ray.weight;
+            colour += shade_hit(comps, rng) * ray.weight;
 
-            float reflect_weight = ray.weight * comps.material.reflective;
+            float2 weights = comps.bounce_weights();
+            float reflect_weight = ray.weight * weights.x;
+            float refract_weight = ray.weight * weights.y;
             if (reflect_weight > EPSILON) {
                 queue.push(Ray {comps.over_point, comps.reflectv}, reflect_weight);
             }
-            
-            // https://en.wikipedia.org/wiki/Snell%27s_law
-            float refract_weight = ray.weight * comps.material.transparency;
-            if (refract_weight > EPSILON){
-                float n_ratio = comps.n1 / comps.n2;
-                float cos_i = dot(comps.eyev, comps.normalv);
-                float sin2_t = n_ratio*n_ratio * (1 - cos_i*cos_i);
-                
-                if (sin2_t < 1){  // not total internal reflection
-                    float cos_t = sqrt(1 - sin2_t);
-                    float4 direction = comps.normalv * (n_ratio * cos_i - cos_t) - comps.eyev * n_ratio;
-                    queue.push(Ray {comps.under_point, direction}, refract_weight);
-                }
+
+            float4 direction;
+            if (refract_weight > EPSILON && comps.refract_direction(direction)) {
+                queue.push(Ray {comps.under_point, direction}, refract_weight);
             }
-            
-            hits.clear();
         }
     }
-    
+
+    if (queue.dropped > 0) {
+        atomic_increment(dropped_rays, queue.dropped);
+    }
+    return colour;
+}
+
+// Follows one random path instead of every reflection and refraction, so there's no queue.
+// At each hit it adds the light that comes straight from the lights, then either reflects, refracts or bounces off in a random direction
+// (chosen by how reflective and transparent the surface is). Bounces off diffuse surfaces are tinted by their colour.
+// Indirect light replaces the ambient term. Emissive surfaces light the scene by being hit on the way.
+float3 WorldView::path_colour(const thread Ray& first_ray, thread Random& rng) const {
+    float3 colour = black();
+    // How much of the light from the rest of the path makes it back to the camera.
+    float3 throughput = float3(1, 1, 1);
+    Ray ray = first_ray;
+    Intersections hits(inputs.max_hits);
+    for (uint32_t bounce=0;bounce<inputs.max_reflect_refract;bounce++) {
+        hits.clear();
+        intersect(ray, hits);
+        if (!hits.has_hit()) break;
+
+        Comps comps = prepare_comps(hits.get_hit(), ray, hits);
+        comps.material.ambient = 0;
+        colour += shade_hit(comps, rng) * throughput;
+
+        float2 weights = comps.bounce_weights();
+        float choice = rng.next_float();
+        float4 direction;
+        if (choice < weights.x) {
+            ray = Ray {comps.over_point, comps.reflectv};
+        } else if (choice < weights.x + weights.y) {
+            if (comps.refract_direction(direction)) {
+                ray = Ray {comps.under_point, direction};
+            } else {
+                ray = Ray {comps.over_point, comps.reflectv};
+            }
+        } else {
+            // Chosen less often on shiny or clear surfaces so it's weighted less automatically.
+            throughput = throughput * comps.colour * comps.material.diffuse;
+            ray = Ray {comps.over_point, cosine_direction(comps.normalv, rng)};
+        }
+
+        // Russian roulette. Dim paths stop early but the ones that survive are brighter to make up for it.
+        // The first few always go on since they matter most. Colours and diffuse can be above 1 so bright paths always survive rather than getting darker.
+        if (bounce >= 2) {
+            float survive = fmin(max3(throughput.x, throughput.y, throughput.z), 1.0f);
+            if (rng.next_float() >= survive) break;
+            throughput = throughput / survive;
+        }
+    }
     return colour;
 }
 
+// Keeps the sign so the slab test still knows which way the ray is going when it's parallel to an axis.
+// Metal's fast-math assumes there's no infinity so can't just divide by zero.
+float safe_inverse(float d) {
+    if (d >= 0) {
+        return 1.0f / fmax(d, 0.00000001f);
+    } else {
+        return 1.0f / fmin(d, -0.00000001f);
+    }
+}
+
+// The distances where a ray enters and leaves the space between two planes perpendicular to one axis.
+float2 slab(float low, float high, float origin, float inv_direction) {
+    float t1 = (low - origin) * inv_direction;
+    float t2 = (high - origin) * inv_direction;
+    return float2(fmin(t1, t2), fmax(t1, t2));
+}
+
+bool BvhNode::hit_by(const thread Ray& ray, float4 inv_direction) const {
+    float2 x = slab(min.x, max.x, ray.origin.x, inv_direction.x);
+    float2 y = slab(min.y, max.y, ray.origin.y, inv_direction.y);
+    float2 z = slab(min.z, max.z, ray.origin.z, inv_direction.z);
+
+    // Boxes behind the ray can be skipped.
+    float tmin = fmax(fmax(x.x, y.x), fmax(z.x, 0.0f));
+    float tmax = fmin(fmin(x.y, y.y), z.y);
+    return tmin <= tmax;
+}
+
+// Walks the BVH with a stack instead of recursion and only tests shapes whose bounding boxes the ray passes through.
 void WorldView::intersect(const thread Ray& ray, thread Intersections& hits) const {
-    for (uint32_t i=0;i<inputs.shape_count;i++){
+    if (inputs.shape_count == 0) return;
+
+    float4 inv_direction = float4(safe_inverse(ray.direction.x), safe_inverse(ray.direction.y), safe_inverse(ray.direction.z), 0);
+    uint32_t stack[BVH_STACK_SIZE];
+    int top = 0;
+    stack[top++] = 0;
+    while (top > 0) {
+        uint32_t i = stack[--top];
+        BvhNode node = bvh[i];
+        if (!node.hit_by(ray, inv_direction)) continue;
+
+        if (node.is_leaf) {
+            Shape shape = shapes[node.index];
+            if (shape.shape == Csg) {
+                intersect_csg(shape, ray, hits);
+            } else {
+                shape.intersect(ray, hits, triangles);
+            }
+        } else {
+            stack[top++] = node.index;
+            stack[top++] = i + 1;
+        }
+    }
+}
+
+// The children aren't in the BVH. They're all intersected into a separate list so the filtering
+// can see where the ray enters and leaves each side (even behind the ray) before the survivors get added to the real list.
+void WorldView::intersect_csg(const thread Shape& csg, const thread Ray& ray, thread Intersections& hits) const {
+    CsgNode root = csgs[csg.csg_index];
+    Intersections children(inputs.max_hits);
+    for (uint32_t i=root.first_left;i<root.end;i++) {
         Shape shape = shapes[i];
-        shape.intersect(ray, hits);
+        shape.intersect(ray, children, triangles);
+    }
+
+    for (uint32_t i=root.first_node;i<=csg.csg_index;i++) {
+        CsgNode node = csgs[i];
+        children.filter_csg(node);
+    }
+
+    for (int i=0;i<children.count;i++) {
+        Intersection hit = children.hits[i];
+        hits.add(hit.t, hit.obj, hit.u, hit.v);
     }
 }
 
-float3 WorldView::shade_hit(const thread Comps& comps) const {
-    float3 colour = black();
+float3 WorldView::shade_hit(const thread Comps& comps, thread Random& rng) const {
+    float3 colour = comps.material.emissive;
     for (uint32_t i=0;i<inputs.light_count;i++){
-        PointLight light = lights[i];
-        bool shadowed = is_shadowed(light.position, comps.over_point);
-        colour += comps.material.lighting(comps.colour, light, comps.over_point, comps.eyev, comps.normalv, shadowed);
+        Light light = lights[i];
+        if (light.from_shape && inputs.integrator == PathTracing) continue;
+        float3 visible = comps.receives_shadow ? intensity_at(light, comps.over_point, rng) : float3(1, 1, 1);
+        colour += comps.material.lighting(comps.colour, light, comps.over_point, comps.eyev, comps.normalv, visible, rng);
     }
     
     return colour;
 }
 
-// TODO: just check for a hit in the range without sorting the whole Intersections 
-bool WorldView::is_shadowed(const thread float4& light_pos, const thread float4& hit_pos) const {
-    float4 light_direction = light_pos - hit_pos;
-    Ray ray = {hit_pos, normalize(light_direction)};
-    Intersections hits;
+// How much of the light reaches the point, averaged over the light's samples.
+float3 WorldView::intensity_at(const thread Light& light, float4 point, thread Random& rng) const {
+    if (!light.shadows) return float3(1, 1, 1);
+    float3 visible = black();
+    for (uint32_t v=0;v<light.vsteps;v++) {
+        for (uint32_t u=0;u<light.usteps;u++) {
+            visible += transmittance(light, light.point_on(u, v, rng), point);
+        }
+    }
+    return visible / light.samples();
+}
+
+float4 Light::point_on(uint32_t u, uint32_t v, thread Random& rng) const {
+    float du = jitter ? rng.next_float() : 0.5f;
+    float dv = jitter ? rng.next_float() : 0.5f;
+    return corner + uvec * (u + du) + vvec * (v + dv);
+}
+
+// Normalized vector from the point towards light_point (one of the samples from point_on).
+// Directional lights ignore light_point since they're the same direction from everywhere.
+float4 Light::direction_from(float4 light_point, float4 point) const {
+    if (light_type == DirectionalLight) return -direction;
+    return normalize(light_point - point);
+}
+
+// How much of the light gets through in this direction (from the light towards the point). Only spot lights have any falloff.
+float Light::spot_falloff(float4 light_direction) const {
+    if (light_type != SpotLight) return 1;
+    float cos_angle = dot(light_direction, direction);
+    if (cos_angle >= cos_inner) return 1;
+    if (cos_angle <= cos_outer) return 0;
+    // Smoothstep so the edge of the cone isn't a visible line.
+    float t = (cos_angle - cos_outer) / (cos_inner - cos_outer);
+    return t * t * (3 - 2 * t);
+}
+
+float Light::attenuation(float4 light_point, float4 point) const {
+    if (light_type == DirectionalLight) return 1;
+    float distance = length(light_point - point);
+    return 1 / (attenuation_constant + attenuation_linear * distance + attenuation_quadratic * distance * distance);
+}
+
+// The fraction of the light (per channel) that gets from light_pos to hit_pos. Black means fully in shadow.
+// Every surface of a transparent shape in the way lets through its transparency, tinted by its colour.
+// Doesn't bend the shadow ray so light isn't focused like real caustics.
+float3 WorldView::transmittance(const thread Light& light, const thread float4& light_pos, const thread float4& hit_pos) const {
+    Ray ray = {hit_pos, light.direction_from(light_pos, hit_pos)};
+    Intersections hits(inputs.max_hits);
     intersect(ray, hits);
-    if (hits.has_hit()) {
-        float t = hits.get_hit().t;
-        // Checks that the hit is not behind the light.
-        return t*t < length_squared(light_direction);
-    } else {
-        return false;
+    float3 transmitted = float3(1, 1, 1);
+    for (int i=0;i<hits.count;i++) {
+        Intersection hit = hits.hits[i];
+        Shape shape = shapes[hit.obj];
+        if (hit.t < 0 || !shape.casts_shadow) continue;
+        // They're sorted so once one is behind the light, the rest are too. Nothing can be behind a directional light.
+        if (light.light_type != DirectionalLight && hit.t*hit.t >= length_squared(light_pos - hit_pos)) break;
+        if (shape.material.transparency <= 0) return black();
+        transmitted = transmitted * shape.material.shadow_tint() * shape.material.transparency;
     }
+    return transmitted;
 }
 
 Comps WorldView::prepare_comps(const thread Intersection& hit, const thread Ray& ray, const thread Intersections& xs) const {
@@ -81,8 +253,11 @@ Comps WorldView::prepare_comps(const thread Intersection& hit, const thread Ray&
     comps.material = object.material;
     comps.point = ray.position(hit.t);
     comps.eyev = -ray.direction;
-    comps.normalv = object.normal_at(comps.point);
+    comps.normalv = object.normal_at(comps.point, hit, triangles);
     comps.inside = dot(comps.normalv, comps.eyev) < 0;
+    comps.receives_shadow = object.receives_shadow;
+    // Before flipping it so bumps stick out of the outside of the shape.
+    comps.normalv = bumped_normal(object, comps.point, comps.normalv);
     if (comps.inside) comps.normalv = -comps.normalv;
 
     // Used for is_shadowed checks to prevent shadow acne
@@ -95,21 +270,63 @@ Comps WorldView::prepare_comps(const thread Intersection& hit, const thread Ray&
     return comps;
 }
 
+// How much of the light is reflected instead of refracted. Approximates the Fresnel equations.
+// https://en.wikipedia.org/wiki/Schlick%27s_approximation
+float Comps::schlick() const {
+    float cos_i = dot(eyev, normalv);
+    if (n1 > n2) {
+        float n_ratio = n1 / n2;
+        float sin2_t = n_ratio*n_ratio * (1 - cos_i*cos_i);
+        if (sin2_t > 1) return 1;  // total internal reflection
+        cos_i = sqrt(1 - sin2_t);
+    }
+
+    float r0 = (n1 - n2) / (n1 + n2);
+    r0 = r0 * r0;
+    // Not pow because it's undefined for negative numbers and rounding might make it a tiny bit below 0.
+    float x = 1 - cos_i;
+    return r0 + (1 - r0) * x*x*x*x*x;
+}
+
+// How much of the colour comes from the reflection and the refraction. Glass reflects more when you look at it from the side.
+float2 Comps::bounce_weights() const {
+    float reflect_weight = material.reflective;
+    float refract_weight = material.transparency;
+    if (material.reflective > 0 && material.transparency > 0) {
+        float reflectance = schlick();
+        reflect_weight *= reflectance;
+        refract_weight *= 1 - reflectance;
+    }
+    return float2(reflect_weight, refract_weight);
+}
+
+// https://en.wikipedia.org/wiki/Snell%27s_law
+// False for total internal reflection.
+bool Comps::refract_direction(thread float4& direction) const {
+    float n_ratio = n1 / n2;
+    float cos_i = dot(eyev, normalv);
+    float sin2_t = n_ratio*n_ratio * (1 - cos_i*cos_i);
+    if (sin2_t >= 1) return false;
+    float cos_t = sqrt(1 - sin2_t);
+    direction = normalv * (n_ratio * cos_i - cos_t) - eyev * n_ratio;
+    return true;
+}
+
 // TODO: really feels like this shouldn't need to use an extra list.
 void WorldView::refraction_path(thread Comps& comps, const thread Intersection& hit, const thread Intersections& xs) const {
-    Intersections containers;
+    Intersections containers(inputs.max_hits);
     for (int i=0;i<xs.count;i++){
         Intersection check = xs.hits[i];
         if (hit == check){
             if (containers.is_empty()) {
                 comps.n1 = 1.0;
             } else {
-                Shape s = shapes[xs.last().obj];
+                Shape s = shapes[containers.last().obj];
                 comps.n1 = s.material.refractive_index;
             }
         }
         
-        int index = containers.index_of(check);
+        int index = containers.index_of(check.obj);
         if (index >= 0){
             containers.remove(index);
         } else {
@@ -120,7 +337,7 @@ void WorldView::refraction_path(thread Comps& comps, const thread Intersection&
             if (containers.is_empty()) {
                 comps.n2 = 1.0;
             } else {
-                Shape s = shapes[xs.last().obj];
+                Shape s = shapes[containers.last().obj];
                 comps.n2 = s.material.refractive_index;
             }
             break;
@@ -130,30 +347,161 @@ void WorldView::refraction_path(thread Comps& comps, const thread Intersection&
 
 float3 WorldView::pattern_colour(Shape object, float4 world_point) const {
     if (object.material.pattern_index < 0) return object.material.colour;
-    Pattern p = patterns[object.material.pattern_index];
-    float4 object_point = object.transform_inverse * world_point;
-    float4 pattern_point = p.transform_inverse * object_point;
+    return pattern_tree_colour(object.material.pattern_index, object.transform_inverse * world_point);
+}
 
-    switch (p.pattern) {
-        case Solid:
-            return p.a;
+// a and b can be other patterns, so the patterns make a tree. Every pattern's colour is a mix of a and b,
+// so instead of recursing, this walks the tree with a stack and adds up each colour scaled by how much of it is used.
+float3 WorldView::pattern_tree_colour(int pattern_index, float4 object_point) const {
+    struct { int index; float4 point; float amount; } stack[PATTERN_STACK_SIZE];
+    int count = 0;
+    stack[count++] = {pattern_index, object_point, 1};
+    float3 colour = black();
+    while (count > 0) {
+        count--;
+        Pattern p = patterns[stack[count].index];
+        float4 pattern_point = p.transform_inverse * stack[count].point;
+        float amount = stack[count].amount;
+        if (p.perturb > 0 && p.pattern != Marble && p.pattern != Wood) {
+            pattern_point = perturb_point(pattern_point, p.perturb, p.noise_scale, p.octaves);
+        }
+
+        switch (p.pattern) {
+            case TextureMap:
+            case UvImage:
+            case UvCheckers:
+            case UvAlignCheck:
+                colour += mapped_colour(p, pattern_point) * amount;
+                continue;
+            default:
+                break;
+        }
+
+        float b_amount = p.b_amount(pattern_point);
+        float amounts[2] = {amount * (1 - b_amount), amount * b_amount};
+        int indices[2] = {p.a_index, p.b_index};
+        float3 colours[2] = {p.a, p.b};
+        for (int i=0;i<2;i++) {
+            if (amounts[i] <= 0) continue;
+            if (indices[i] < 0) {
+                colour += colours[i] * amounts[i];
+            } else if (count < PATTERN_STACK_SIZE) {
+                // The nested pattern's transform is relative to this one.
+                stack[count++] = {indices[i], pattern_point, amounts[i]};
+            }
+        }
+    }
+    return colour;
+}
+
+// How much of b to use. Most patterns are all a or all b at any point but some mix them.
+float Pattern::b_amount(float4 pattern_point) const {
+    switch (pattern) {
         case Stripes: {
             int which = (int) floor(pattern_point.x);
-            return which % 2 == 0 ? p.a : p.b;
-        }
-        case Gradient: {
-            float3 distance = p.b - p.a;
-            float t = pattern_point.x - floor(pattern_point.x);
-            return p.a + (distance * t);
+            return which % 2 == 0 ? 0 : 1;
         }
+        case Gradient:
+            return pattern_point.x - floor(pattern_point.x);
         case Ring: {
             float dist = sqrt((pattern_point.x * pattern_point.x) + (pattern_point.z * pattern_point.z));
             int which = (int) floor(dist);
-            return which % 2 == 0 ? p.a : p.b;
+            return which % 2 == 0 ? 0 : 1;
         }
         case Checker: {
             int which = (int) (floor(pattern_point.x) + floor(pattern_point.y) + floor(pattern_point.z));
+            return which % 2 == 0 ? 0 : 1;
+        }
+        case Blend:
+            return weight;
+        case Marble:
+            return marble(pattern_point, perturb, noise_scale, octaves);
+        case Wood:
+            return wood(pattern_point, perturb, noise_scale, octaves);
+        case Clouds:
+            return clouds(pattern_point, noise_scale, octaves);
+        case Ripples:
+            return ripples(pattern_point);
+        default:
+            return 0;
+    }
+}
+
+// The patterns that turn the point into uv coordinates first. Without a TextureMap, uv patterns are planar.
+float3 WorldView::mapped_colour(Pattern p, float4 pattern_point) const {
+    if (p.pattern != TextureMap) return uv_pattern_colour(p, planar_map(pattern_point));
+    switch (p.mapping) {
+        case SphericalMap:
+            return uv_pattern_colour(patterns[p.uv_pattern_index], spherical_map(pattern_point));
+        case PlanarMap:
+            return uv_pattern_colour(patterns[p.uv_pattern_index], planar_map(pattern_point));
+        case CylindricalMap:
+            return uv_pattern_colour(patterns[p.uv_pattern_index], cylindrical_map(pattern_point));
+        case CubeMap: {
+            CubeFace face = face_from_point(pattern_point);
+            return uv_pattern_colour(patterns[p.uv_pattern_index + face], cube_uv(face, pattern_point));
+        }
+        default:
+            return uv_pattern_colour(patterns[p.uv_pattern_index], planar_map(pattern_point));
+    }
+}
+
+float3 WorldView::uv_pattern_colour(Pattern p, float2 uv) const {
+    switch (p.pattern) {
+        case UvImage:
+            return texel_colour(textures[p.texture_index], texels, uv);
+        case UvCheckers: {
+            int which = (int) (floor(uv.x * p.width) + floor(uv.y * p.height));
             return which % 2 == 0 ? p.a : p.b;
         }
+        case UvAlignCheck: {
+            if (uv.y > 0.8f) {
+                if (uv.x < 0.2f) return p.corners[UpperLeft];
+                if (uv.x > 0.8f) return p.corners[UpperRight];
+            } else if (uv.y < 0.2f) {
+                if (uv.x < 0.2f) return p.corners[BottomLeft];
+                if (uv.x > 0.8f) return p.corners[BottomRight];
+            }
+            return p.a;
+        }
+        default:
+            return p.a;
+    }
+}
+
+// The bump pattern is in the same space as the colour pattern, so it moves with the shape.
+float4 WorldView::bumped_normal(Shape object, float4 world_point, float4 normal) const {
+    Material m = object.material;
+    if (m.bump_index < 0) return normal;
+
+    if (m.bump == NormalMap) {
+        // Colours go from 0 to 1 but each part of a normal goes from -1 to 1.
+        float3 bent = pattern_tree_colour(m.bump_index, object.transform_inverse * world_point) * 2 - float3(1, 1, 1);
+        // u goes around the shape's y axis and v goes up it like the spherical and cylindrical maps.
+        // Where the surface faces along y, u goes along x and v along z like the planar map.
+        float4 up = object.normal_to_world(vector(0, 1, 0));
+        if (abs(dot(up, normal)) > 0.999f) up = object.normal_to_world(vector(0, 0, 1));
+        float4 tangent = normalize(cross(normal, up));
+        float4 bitangent = cross(tangent, normal);
+        return normalize(tangent * (bent.x * m.bump_amount) + bitangent * (bent.y * m.bump_amount) + normal * bent.z);
     }
+
+    // The slope of the height in each direction. Only the part along the surface bends the normal.
+    float step = 0.001f;
+    float4 dx = vector(step, 0, 0);
+    float4 dy = vector(0, step, 0);
+    float4 dz = vector(0, 0, step);
+    float4 slope = vector(
+        bump_height(object, world_point + dx) - bump_height(object, world_point - dx),
+        bump_height(object, world_point + dy) - bump_height(object, world_point - dy),
+        bump_height(object, world_point + dz) - bump_height(object, world_point - dz)
+    ) / (2 * step);
+    slope = slope - normal * dot(slope, normal);
+    return normalize(normal - slope * m.bump_amount);
+}
+
+// The brightness of a HeightMap.
+float WorldView::bump_height(Shape object, float4 world_point) const {
+    float3 colour = pattern_tree_colour(object.material.bump_index, object.transform_inverse * world_point);
+    return (colour.x + colour.y + colour.z) / 3;
 }
@@ -5,15 +5,91 @@
 #include "shapes.h"
 #include "ray.h"
 
-typedef struct PointLight {
+typedef enum LightType {
+    // Area lights are point lights too, just with more than one point.
+    PointLight,
+    // A point light that only shines within a cone around its direction.
+    SpotLight,
+    // Infinitely far away (like the sun) so every ray towards it is parallel and there's no position.
+    DirectionalLight
+} LightType;
+
+// A rectangle split into usteps * vsteps cells with a sample in each. Shadows get softer the more of the samples a point can see.
+// A point light is just one cell with no size.
+typedef struct Light {
+    // The middle of the rectangle.
     float4 position;
     float3 intensity;
-} PointLight;
+    float4 corner;
+    // The edges of one cell (the whole edge divided by the number of steps).
+    float4 uvec;
+    float4 vvec;
+    // Which way the light shines for spot and directional lights. Normalized.
+    float4 direction;
+    LightType light_type;
+    uint32_t usteps;
+    uint32_t vsteps;
+    // Cosines of the spot light's cone angles. Full brightness inside the inner one, fading to nothing at the outer one.
+    float cos_inner;
+    float cos_outer;
+    // Brightness is divided by constant + linear*d + quadratic*d^2 at distance d. The default (1, 0, 0) doesn't fade at all.
+    // Directional lights ignore it since they're infinitely far away.
+    float attenuation_constant;
+    float attenuation_linear;
+    float attenuation_quadratic;
+    // Samples go somewhere random in their cell instead of the middle. It trades banding for noise.
+    bool jitter;
+    // When false, nothing blocks this light so it doesn't need any shadow rays.
+    bool shadows;
+    // Stands in for an emissive shape (see area-light in scene.rs). Path tracing skips it since paths find the shape's light by hitting it.
+    bool from_shape;
+
+    float4 point_on(uint32_t u, uint32_t v, thread Random& rng) const;
+    float4 direction_from(float4 light_point, float4 point) const;
+    float spot_falloff(float4 light_direction) const;
+    float attenuation(float4 light_point, float4 point) const;
+    uint32_t samples() const {
+        return usteps * vsteps;
+    }
+} Light;
+
+// Built by bvh.rs over the world space bounds of every shape. Nodes are stored depth first so a parent's first child is always the next node.
+typedef struct BvhNode {
+    float4 min;
+    float4 max;
+    // For leaves, the index of the shape. Otherwise, the index of the second child.
+    uint32_t index;
+    bool is_leaf;
+
+    bool hit_by(const thread Ray& ray, float4 inv_direction) const;
+} BvhNode;
+
+// How the colour of a ray is worked out.
+typedef enum Integrator {
+    // The book's way. Light only comes straight from lights (plus ambient), then mirror reflections and refractions.
+    WhittedTracing,
+    // Monte Carlo path tracing. Each sample follows one random path so light bounces between diffuse surfaces too.
+    // Noisy unless there are lots of samples or frames get averaged.
+    PathTracing
+} Integrator;
 
 typedef struct {
     Camera camera;
     uint32_t shape_count;
     uint32_t light_count;
+    // How many rays colour_at traces for one pixel (the first one plus reflections and refractions).
+    // For path tracing, how many times a path can bounce.
+    uint32_t max_reflect_refract;
+    // At most MAX_RAY_QUEUE.
+    uint32_t max_ray_queue;
+    // At most MAX_HITS.
+    uint32_t max_hits;
+    // Rays per pixel. Their colours are averaged to smooth out jagged edges. At most MAX_SAMPLES.
+    uint32_t samples;
+    SamplePattern sample_pattern;
+    Integrator integrator;
+    // Mixed into the random seeds so each frame gets different random numbers. Renderers that average frames count it up.
+    uint32_t frame_index;
 } ShaderInputs;
 
 typedef struct Comps {
@@ -21,6 +97,7 @@ typedef struct Comps {
     float n1;
     float n2;
     bool inside;
+    bool receives_shadow;
     Material material;
     float3 colour;
     float4 point;
@@ -29,23 +106,43 @@ typedef struct Comps {
     float4 over_point;
     float4 reflectv;
     float4 under_point;
+
+    float schlick() const;
+    float2 bounce_weights() const;
+    bool refract_direction(thread float4& direction) const;
 } Comps;
 
 typedef struct WorldView {
     const device Shape* shapes;
-    const device PointLight* lights;
+    const device Light* lights;
     const device Pattern* patterns;
+    const device Texture* textures;
+    const device uint32_t* texels;
+    const device TriangleData* triangles;
+    const device BvhNode* bvh;
+    const device CsgNode* csgs;
+    // Counts rays that didn't fit in the RayQueue.
+    device atomic_uint* dropped_rays;
     ShaderInputs inputs;
-    WorldView(const device Shape* s, const device PointLight* l, const constant ShaderInputs& i, const device Pattern* p)
-            : shapes(s), lights(l), patterns(p), inputs(i) {};
+    WorldView(const device Shape* s, const device Light* l, const constant ShaderInputs& i, const device Pattern* p, const device TriangleData* t, const device BvhNode* b, const device CsgNode* c, device atomic_uint* d, const device Texture* tx, const device uint32_t* tl)
+            : shapes(s), lights(l), patterns(p), textures(tx), texels(tl), triangles(t), bvh(b), csgs(c), dropped_rays(d), inputs(i) {};
 
-    float3 colour_at(const thread Ray& ray) const;
+    float3 pixel_colour(float x, float y) const;
+    float3 colour_at(const thread Ray& ray, thread Random& rng) const;
+    float3 path_colour(const thread Ray& ray, thread Random& rng) const;
     void intersect(const thread Ray& ray, thread Intersections& hits) const;
-    float3 shade_hit(const thread Comps& comps) const;
-    bool is_shadowed(const thread float4& light_pos, const thread float4& hit_pos) const;
+    void intersect_csg(const thread Shape& csg, const thread Ray& ray, thread Intersections& hits) const;
+    float3 shade_hit(const thread Comps& comps, thread Random& rng) const;
+    float3 intensity_at(const thread Light& light, float4 point, thread Random& rng) const;
+    float3 transmittance(const thread Light& light, const thread float4& light_pos, const thread float4& hit_pos) const;
     Comps prepare_comps(const thread Intersection& hit, const thread Ray& ray, const thread Intersections& xs) const;
     void refraction_path(thread Comps&, const thread Intersection&, const thread Intersections&) const;
     float3 pattern_colour(Shape m, float4 point) const;
+    float3 pattern_tree_colour(int pattern_index, float4 object_point) const;
+    float4 bumped_normal(Shape object, float4 point, float4 normal) const;
+    float bump_height(Shape object, float4 point) const;
+    float3 mapped_colour(Pattern p, float4 pattern_point) const;
+    float3 uv_pattern_colour(Pattern p, float2 uv) const;
 } WorldView;
 
 #endif 
@@ -1,5 +1,4 @@
 use glam::{vec4, Vec3A};
-use rayon::iter::IntoParallelIterator;
 use rayon::prelude::*;
 use raytracer::window::{AppState, RenderStrategy};
 use softbuffer::GraphicsContext;
@@ -12,6 +11,10 @@ fn main() {
 struct CpuState {
     graphics_context: GraphicsContext,
     screen_buffer: Vec<u32>,
+    /// Every frame's colours added up since the view last changed. Random samples (jittered, soft shadows, path tracing)
+    /// are different each frame so the average gets smoother the longer the camera stays still.
+    accumulated: Vec<Vec3A>,
+    frames: u32,
 }
 
 impl RenderStrategy for CpuState {
@@ -20,6 +23,8 @@ impl RenderStrategy for CpuState {
         CpuState {
             graphics_context: unsafe { GraphicsContext::new(&app.window, &app.window) }.unwrap(),
             screen_buffer: vec![],
+            accumulated: vec![],
+            frames: 0,
         }
     }
 
@@ -29,33 +34,46 @@ impl RenderStrategy for CpuState {
             app.window.inner_size().width,
             app.window.inner_size().height,
         );
-        let world = &app.world.view();
+        if self.accumulated.len() != (width * height) as usize {
+            self.view_changed();
+            self.accumulated.resize((width * height) as usize, Vec3A::ZERO);
+        }
+        let mut world = app.world.view();
+        // Different random numbers each frame or there'd be nothing new to average.
+        world.inputs.frame_index = self.frames;
+        let world = &world;
         let scale = app.window.scale_factor() as f32;
 
-        (0..(width * height))
-            .into_par_iter()
-            .map(|i| {
-                let (x, y) = ((i % width) as f32 / scale, (i / width) as f32 / scale);
-
-                let colour = unsafe {
-                    let ray = world.inputs.camera.ray_for_pixel(x, y);
-                    world.colour_at(&ray)
-                };
+        self.accumulated.par_iter_mut().enumerate().for_each(|(i, sum)| {
+            let i = i as u32;
+            let (x, y) = ((i % width) as f32 / scale, (i / width) as f32 / scale);
+            *sum += unsafe { world.pixel_colour(x, y) };
+        });
+        self.frames += 1;
 
-                to_packed_colour(colour)
-            })
+        let frames = self.frames as f32;
+        self.accumulated
+            .par_iter()
+            .map(|sum| to_packed_colour(*sum / frames))
             .collect_into_vec(&mut self.screen_buffer);
 
         self.graphics_context
             .set_buffer(&self.screen_buffer, width as u16, height as u16);
+        let samples = self.frames * app.world.settings.samples;
+        app.window.set_title(&format!("Rusty Raytracer ({samples} samples per pixel)"));
     }
 
     fn resized(&mut self, _size: LogicalSize<u32>) {
-        // NO-OP
+        self.view_changed();
     }
 
     fn world_changed(&mut self, _app: &AppState) {
-        // NO-OP
+        self.view_changed();
+    }
+
+    fn view_changed(&mut self) {
+        self.accumulated.fill(Vec3A::ZERO);
+        self.frames = 0;
     }
 }
 
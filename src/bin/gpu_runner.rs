@@ -25,6 +25,12 @@ struct GpuState {
     shapes_buffer: Buffer,
     lights_buffer: Buffer,
     patterns_buffer: Buffer,
+    triangles_buffer: Buffer,
+    bvh_buffer: Buffer,
+    csgs_buffer: Buffer,
+    dropped_rays_buffer: Buffer,
+    textures_buffer: Buffer,
+    texels_buffer: Buffer,
     device: Device,
 }
 
@@ -42,6 +48,12 @@ impl RenderStrategy for GpuState {
             shapes_buffer: init_buffer(&device, app.world.get_shapes()),
             lights_buffer: init_buffer(&device, app.world.get_lights()),
             patterns_buffer: init_buffer(&device, app.world.get_patterns()),
+            triangles_buffer: init_buffer(&device, app.world.get_triangles()),
+            bvh_buffer: init_buffer(&device, app.world.get_bvh()),
+            csgs_buffer: init_buffer(&device, app.world.get_csgs()),
+            dropped_rays_buffer: device.new_buffer(mem::size_of::<u32>() as u64, MTLResourceOptions::StorageModeShared),
+            textures_buffer: init_buffer(&device, app.world.get_textures()),
+            texels_buffer: init_buffer(&device, app.world.get_texels()),
             device,
         }
     }
@@ -59,11 +71,21 @@ impl RenderStrategy for GpuState {
         self.shapes_buffer = init_buffer(&self.device, app.world.get_shapes());
         self.lights_buffer = init_buffer(&self.device, app.world.get_lights());
         self.patterns_buffer = init_buffer(&self.device, app.world.get_patterns());
+        self.triangles_buffer = init_buffer(&self.device, app.world.get_triangles());
+        self.bvh_buffer = init_buffer(&self.device, app.world.get_bvh());
+        self.csgs_buffer = init_buffer(&self.device, app.world.get_csgs());
+        self.textures_buffer = init_buffer(&self.device, app.world.get_textures());
+        self.texels_buffer = init_buffer(&self.device, app.world.get_texels());
+    }
+
+    fn view_changed(&mut self) {
+        // NO-OP
     }
 }
 
 impl GpuState {
     fn do_render(&mut self, app: &AppState) {
+        self.read_dropped_rays(app);
         let drawable = self.layer.next_drawable().unwrap();
         let pass_descriptor = RenderPassDescriptor::new();
         init_pass(pass_descriptor, drawable.texture());
@@ -83,11 +105,27 @@ impl GpuState {
         encoder.set_fragment_bytes(
             0,
             mem::size_of::<ShaderInputs>() as u64,
-            ptr(&app.world.view().inputs),
+            ptr(&app.world.inputs()),
         );
         encoder.set_fragment_buffer(1, Some(&self.shapes_buffer), 0);
         encoder.set_fragment_buffer(2, Some(&self.lights_buffer), 0);
         encoder.set_fragment_buffer(3, Some(&self.patterns_buffer), 0);
+        encoder.set_fragment_buffer(4, Some(&self.triangles_buffer), 0);
+        encoder.set_fragment_buffer(5, Some(&self.bvh_buffer), 0);
+        encoder.set_fragment_buffer(6, Some(&self.csgs_buffer), 0);
+        encoder.set_fragment_buffer(7, Some(&self.dropped_rays_buffer), 0);
+        encoder.set_fragment_buffer(8, Some(&self.textures_buffer), 0);
+        encoder.set_fragment_buffer(9, Some(&self.texels_buffer), 0);
+    }
+
+    /// Hands the count over to the World and starts again from zero. It doesn't wait for the last frame
+    /// to finish so the count might go to the wrong frame but it's only used to warn about the settings.
+    fn read_dropped_rays(&self, app: &AppState) {
+        let count = self.dropped_rays_buffer.contents() as *mut u32;
+        unsafe {
+            app.world.add_dropped_rays(*count);
+            *count = 0;
+        }
     }
 }
 
@@ -0,0 +1,110 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use std::{env, fs, process};
+
+use glam::Vec3A;
+use rayon::prelude::*;
+use raytracer::scene::load_scene;
+use raytracer::shader_types::World;
+
+/// Renders a scene without opening a window so it works on machines with no display.
+/// Runs the shaders on the CPU like cpu_runner, so compile with --release.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        usage();
+    }
+
+    let definition = fs::read_to_string(&args[1]).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {e}", args[1]);
+        process::exit(1);
+    });
+    let mut world = load_scene(&definition).unwrap_or_else(|e| {
+        eprintln!("Failed to parse {}: {e}", args[1]);
+        process::exit(1);
+    });
+
+    if let Some(size) = args.get(3) {
+        let (width, height) = parse_size(size).unwrap_or_else(|| usage());
+        world.camera.resize(width, height);
+    }
+
+    let (width, height) = (world.camera.hsize as u32, world.camera.vsize as u32);
+    println!("{}", world.bvh_stats());
+    println!("Rendering {} at {}x{} with {} samples per pixel.", args[1], width, height, world.settings.samples);
+    let start = Instant::now();
+    let pixels = render(&world, width, height);
+    println!("Finished in {} ms.", start.elapsed().as_millis());
+    let dropped = world.take_dropped_rays();
+    if dropped > 0 {
+        println!("{} rays didn't fit in the ray queue. Try a bigger max-ray-queue in the scene's settings.", dropped);
+    }
+
+    let output = PathBuf::from(&args[2]);
+    let ppm = output.with_extension("ppm");
+    let png = output.with_extension("png");
+    write_ppm(&ppm, width, height, &pixels).expect("Failed to write ppm.");
+    write_png(&png, width, height, &pixels).expect("Failed to write png.");
+    println!("Saved {} and {}.", ppm.display(), png.display());
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: render <scene.yml> <output> [WIDTHxHEIGHT]");
+    process::exit(1);
+}
+
+fn parse_size(size: &str) -> Option<(usize, usize)> {
+    let (width, height) = size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    if width == 0 || height == 0 {
+        None
+    } else {
+        Some((width, height))
+    }
+}
+
+/// Same as CpuState::render but there's no window so no scale factor to worry about.
+fn render(world: &World, width: u32, height: u32) -> Vec<[u8; 3]> {
+    let world = &world.view();
+    let mut pixels = vec![];
+    (0..(width * height))
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = ((i % width) as f32, (i / width) as f32);
+
+            let colour = unsafe { world.pixel_colour(x, y) };
+
+            to_bytes(colour)
+        })
+        .collect_into_vec(&mut pixels);
+    pixels
+}
+
+fn to_bytes(v: Vec3A) -> [u8; 3] {
+    [clamp_colour(v.x), clamp_colour(v.y), clamp_colour(v.z)]
+}
+
+fn clamp_colour(f: f32) -> u8 {
+    (f.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Plain text format from chapter 2 of the book. One pixel per line keeps every line under 70 characters.
+fn write_ppm(path: &Path, width: u32, height: u32, pixels: &[[u8; 3]]) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "P3\n{} {}\n255", width, height)?;
+    for [r, g, b] in pixels {
+        writeln!(out, "{} {} {}", r, g, b)?;
+    }
+    out.flush()
+}
+
+fn write_png(path: &Path, width: u32, height: u32, pixels: &[[u8; 3]]) -> Result<(), png::EncodingError> {
+    let out = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(out, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels.concat().as_slice())
+}
@@ -0,0 +1,284 @@
+use std::fmt;
+
+use glam::{vec3, Vec3, Vec4};
+use crate::shader_types::{BvhNode, CsgNode, CsgOperation, Shape, ShapeType, TriangleData, BVH_STACK_SIZE};
+
+/// Planes and open cylinders go on forever but the shader can't handle infinity (fast-math),
+/// so their boxes are just really big instead.
+const UNBOUNDED: f32 = 1.0e9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Bounds {
+    pub const EVERYTHING: Bounds = Bounds {
+        min: Vec3::splat(-UNBOUNDED),
+        max: Vec3::splat(UNBOUNDED),
+    };
+
+    const EMPTY: Bounds = Bounds {
+        min: Vec3::splat(f32::MAX),
+        max: Vec3::splat(-f32::MAX),
+    };
+
+    fn of_points(points: impl IntoIterator<Item = Vec3>) -> Bounds {
+        points.into_iter().fold(Bounds::EMPTY, |bounds, p| Bounds {
+            min: bounds.min.min(p),
+            max: bounds.max.max(p),
+        })
+    }
+
+    pub(crate) fn union(self, other: Bounds) -> Bounds {
+        Bounds {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    fn centre(self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    pub(crate) fn is_unbounded(self) -> bool {
+        self.min.min_element() <= -UNBOUNDED || self.max.max_element() >= UNBOUNDED
+    }
+
+    fn corners(self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            vec3(a.x, a.y, a.z),
+            vec3(a.x, a.y, b.z),
+            vec3(a.x, b.y, a.z),
+            vec3(a.x, b.y, b.z),
+            vec3(b.x, a.y, a.z),
+            vec3(b.x, a.y, b.z),
+            vec3(b.x, b.y, a.z),
+            vec3(b.x, b.y, b.z),
+        ]
+    }
+}
+
+/// The box around the shape in object space, before its transform is applied.
+fn local_bounds(shape: &Shape, triangles: &[TriangleData]) -> Bounds {
+    match shape.shape {
+        ShapeType::Sphere | ShapeType::Cube => Bounds {
+            min: Vec3::splat(-1.0),
+            max: Vec3::splat(1.0),
+        },
+        ShapeType::Plane => Bounds::EVERYTHING,
+        ShapeType::Cylinder => Bounds {
+            min: vec3(-1.0, shape.minimum, -1.0),
+            max: vec3(1.0, shape.maximum, 1.0),
+        },
+        ShapeType::Cone => {
+            let radius = shape.minimum.abs().max(shape.maximum.abs());
+            Bounds {
+                min: vec3(-radius, shape.minimum, -radius),
+                max: vec3(radius, shape.maximum, radius),
+            }
+        }
+        ShapeType::Triangle | ShapeType::SmoothTriangle => {
+            let t = &triangles[shape.triangle_index as usize];
+            Bounds::of_points([t.p1, t.p2, t.p3].map(Vec4::truncate))
+        }
+        // Depends on the other shapes so build uses csg_bounds instead.
+        ShapeType::Csg => Bounds::EVERYTHING,
+    }
+}
+
+/// Transforms every corner of the local box and takes the box around those.
+pub fn world_bounds(shape: &Shape, triangles: &[TriangleData]) -> Bounds {
+    let local = local_bounds(shape, triangles);
+    if local.is_unbounded() {
+        return Bounds::EVERYTHING;
+    }
+
+    let transform = shape.transform_inverse.inverse();
+    Bounds::of_points(local.corners().map(|p| transform.transform_point3(p)))
+}
+
+/// The children are already in world space. Only intersections and differences can be cut down to the left side.
+fn csg_bounds(shape: &Shape, shapes: &[Shape], triangles: &[TriangleData], csgs: &[CsgNode]) -> Bounds {
+    let root = &csgs[shape.csg_index as usize];
+    let end = match root.operation {
+        CsgOperation::CsgUnion => root.end,
+        CsgOperation::CsgIntersection | CsgOperation::CsgDifference => root.first_right,
+    };
+    shapes[root.first_left as usize..end as usize]
+        .iter()
+        .fold(Bounds::EMPTY, |b, s| b.union(world_bounds(s, triangles)))
+}
+
+/// Flattens a tree over every shape into the order the shader walks it (see BvhNode in world.h).
+/// Each leaf holds one shape. Empty if there are no shapes.
+/// Shapes inside a CSG are skipped because the Csg shape intersects them itself.
+/// Errors with the tree's stats if it's too deep for the shader's stack.
+pub fn build(shapes: &[Shape], triangles: &[TriangleData], csgs: &[CsgNode]) -> Result<Vec<BvhNode>, BvhStats> {
+    let mut in_csg = vec![false; shapes.len()];
+    for node in csgs {
+        in_csg[node.first_left as usize..node.end as usize].fill(true);
+    }
+
+    let (mut unbounded, mut bounded): (Vec<_>, Vec<_>) = shapes
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !in_csg[*i])
+        .map(|(i, shape)| {
+            let bounds = if shape.shape == ShapeType::Csg {
+                csg_bounds(shape, shapes, triangles, csgs)
+            } else {
+                world_bounds(shape, triangles)
+            };
+            (i as u32, bounds)
+        })
+        .partition(|(_, bounds)| bounds.is_unbounded());
+
+    // Infinite shapes get their own branch so they don't make every other box huge.
+    let mut nodes = vec![];
+    if unbounded.is_empty() || bounded.is_empty() {
+        unbounded.append(&mut bounded);
+        if !unbounded.is_empty() {
+            build_node(&mut nodes, &mut unbounded);
+        }
+    } else {
+        nodes.push(node(Bounds::EVERYTHING, 0, false));
+        build_node(&mut nodes, &mut unbounded);
+        nodes[0].index = nodes.len() as u32;
+        build_node(&mut nodes, &mut bounded);
+    }
+
+    let stats = BvhStats::of(&nodes);
+    if stats.depth >= BVH_STACK_SIZE as usize {
+        return Err(stats);
+    }
+    Ok(nodes)
+}
+
+/// Splits at the median along whichever axis the centres are most spread out on.
+fn build_node(nodes: &mut Vec<BvhNode>, shapes: &mut [(u32, Bounds)]) {
+    let bounds = shapes.iter().fold(Bounds::EMPTY, |b, (_, s)| b.union(*s));
+    if let [(index, _)] = shapes {
+        nodes.push(node(bounds, *index, true));
+        return;
+    }
+
+    let centres = Bounds::of_points(shapes.iter().map(|(_, s)| s.centre()));
+    let size = centres.max - centres.min;
+    let axis = if size.x >= size.y && size.x >= size.z {
+        0
+    } else if size.y >= size.z {
+        1
+    } else {
+        2
+    };
+    shapes.sort_by(|(_, a), (_, b)| a.centre()[axis].total_cmp(&b.centre()[axis]));
+
+    let (first, second) = shapes.split_at_mut(shapes.len() / 2);
+    let parent = nodes.len();
+    nodes.push(node(bounds, 0, false));
+    build_node(nodes, first);
+    nodes[parent].index = nodes.len() as u32;
+    build_node(nodes, second);
+}
+
+fn node(bounds: Bounds, index: u32, is_leaf: bool) -> BvhNode {
+    BvhNode {
+        min: bounds.min.extend(1.0),
+        max: bounds.max.extend(1.0),
+        index,
+        is_leaf,
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    /// Number of nodes on the longest path from the root to a leaf.
+    pub depth: usize,
+}
+
+impl BvhStats {
+    pub fn of(nodes: &[BvhNode]) -> BvhStats {
+        let mut stats = BvhStats {
+            nodes: nodes.len(),
+            ..Default::default()
+        };
+        let mut stack = vec![];
+        if !nodes.is_empty() {
+            stack.push((0, 1));
+        }
+        while let Some((i, depth)) = stack.pop() {
+            let node = &nodes[i];
+            stats.depth = stats.depth.max(depth);
+            if node.is_leaf {
+                stats.leaves += 1;
+            } else {
+                stack.push((i + 1, depth + 1));
+                stack.push((node.index as usize, depth + 1));
+            }
+        }
+        stats
+    }
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BVH has {} nodes ({} leaves) and a depth of {}.", self.nodes, self.leaves, self.depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scene::{load_scene, SCENE_FILES};
+    use crate::shader_types::{Intersections, Ray, World};
+
+    /// What WorldView::intersect did before there was a BVH.
+    fn intersect_every_shape(world: &World, ray: &Ray) -> Vec<(u32, f32)> {
+        let mut hits = Intersections::default();
+        for shape in &world.shapes {
+            unsafe { shape.intersect(ray, &mut hits, world.triangles.as_ptr()) };
+        }
+        sorted(hits)
+    }
+
+    fn sorted(hits: Intersections) -> Vec<(u32, f32)> {
+        let mut hits: Vec<_> = hits.hits[..hits.count as usize].iter().map(|h| (h.obj, h.t)).collect();
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+        hits
+    }
+
+    #[test]
+    fn finds_the_same_hits_as_testing_every_shape() {
+        for scene in SCENE_FILES {
+            let mut world = load_scene(scene).unwrap();
+            world.camera.resize(40, 30);
+            let view = world.view();
+            for y in 0..30 {
+                for x in 0..40 {
+                    let ray = unsafe { world.camera.ray_for_pixel(x as f32, y as f32) };
+                    let mut hits = Intersections::default();
+                    unsafe { view.intersect(&ray, &mut hits) };
+                    let visible = |h: &(u32, f32)| h.1 >= 0.0;
+                    let expected: Vec<_> = intersect_every_shape(&world, &ray).into_iter().filter(visible).collect();
+                    let found: Vec<_> = sorted(hits).into_iter().filter(visible).collect();
+                    assert_eq!(found, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_shape_gets_a_leaf() {
+        for scene in SCENE_FILES {
+            let world = load_scene(scene).unwrap();
+            let stats = world.bvh_stats();
+            let in_csg = |i: usize| world.get_csgs().iter().any(|c| (c.first_left as usize..c.end as usize).contains(&i));
+            assert_eq!(stats.leaves, (0..world.shapes.len()).filter(|&i| !in_csg(i)).count());
+            assert_eq!(stats.nodes, stats.leaves * 2 - 1);
+        }
+    }
+}
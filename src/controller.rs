@@ -47,16 +47,24 @@ impl CameraController {
         self.rotation.y += delta_mouse.1 as f32;
     }
 
-    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+    /// Returns true if the camera moved.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) -> bool {
+        self.rotation = Vec2::ZERO;
+        let direction = self.direction();
+        // Taking the matrix apart and putting it back together isn't exact so leave it alone when nothing's held down.
+        if direction == Vec3::ZERO {
+            return false;
+        }
+
         let last_matrix = camera.get_transform();
         let (scale, rotation, mut translation) = last_matrix.to_scale_rotation_translation();
-        translation += self.direction() * dt * MOVE_SPEED;
+        translation += direction * dt * MOVE_SPEED;
         camera.set_transform(Mat4::from_scale_rotation_translation(
             scale,
             rotation,
             translation,
         ));
-        self.rotation = Vec2::ZERO;
+        true
     }
 
     fn direction(&self) -> Vec3 {
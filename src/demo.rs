@@ -1,6 +1,6 @@
 use std::f32::consts::PI;
 
-use crate::shader_types::{Camera, PatternType, PointLight, Shape, ShapeType, World};
+use crate::shader_types::{Camera, PatternType, Light, Shape, ShapeType, World};
 
 use glam::{vec3, vec3a, vec4, Mat4, Vec3A};
 use crate::bindings::Pattern;
@@ -15,10 +15,7 @@ fn base_world() -> World {
         vec3(0.0, 1.0, 0.0),
         vec3(0.0, 1.0, 0.0),
     ));
-    world.add_light(PointLight {
-        position: vec4(-10.0, 10.0, -10.0, 1.0),
-        intensity: Vec3A::new(1.0, 1.0, 1.0),
-    });
+    world.add_light(Light::point(vec4(-10.0, 10.0, -10.0, 1.0), Vec3A::new(1.0, 1.0, 1.0)));
 
     world
 }
@@ -35,9 +32,8 @@ pub fn chapter10() -> World {
     middle.material.pattern_index = world.add_pattern(Pattern {
         a: vec3a(0.5, 0.5, 0.5),
         b: vec3a(0.8, 0.2, 0.2),
-        pattern: PatternType::Stripes,
-        __bindgen_padding_0: 0,
         transform_inverse: Mat4::IDENTITY,
+        ..PatternType::Stripes.create()
     });
     world.add_shape(middle);
 
@@ -51,9 +47,8 @@ pub fn chapter10() -> World {
     left.material.pattern_index = world.add_pattern(Pattern {
         a: vec3a(0.5, 0.5, 0.5),
         b: vec3a(0.8, 0.2, 0.2),
-        pattern: PatternType::Stripes,
-        __bindgen_padding_0: 0,
         transform_inverse: Mat4::IDENTITY,
+        ..PatternType::Stripes.create()
     });
     world.add_shape(left);
 
@@ -67,9 +62,8 @@ pub fn chapter10() -> World {
     right.material.pattern_index = world.add_pattern(Pattern {
         a: vec3a(0.0, 0.0, 1.0),
         b: vec3a(1.0, 0.0, 0.0),
-        pattern: PatternType::Gradient,
-        __bindgen_padding_0: 0,
         transform_inverse: Mat4::from_scale(vec3(0.5, 0.5, 0.5)) * Mat4::from_translation(vec3(1.0, 0.0, 0.0)),
+        ..PatternType::Gradient.create()
     });
     world.add_shape(right);
 
@@ -83,9 +77,8 @@ pub fn chapter10() -> World {
     another.material.pattern_index = world.add_pattern(Pattern {
         a: vec3a(1.0, 1.0, 1.0),
         b: vec3a(0.0, 1.0, 0.0),
-        pattern: PatternType::Checker,
-        __bindgen_padding_0: 0,
         transform_inverse: Mat4::from_scale(vec3(4.0, 4.0, 4.0)),
+        ..PatternType::Checker.create()
     });
     world.add_shape(another);
 
@@ -96,9 +89,8 @@ pub fn chapter10() -> World {
     floor.material.pattern_index = world.add_pattern(Pattern {
         a: vec3a(0.5, 0.5, 0.5),
         b: vec3a(0.8, 0.2, 0.2),
-        pattern: PatternType::Ring,
-        __bindgen_padding_0: 0,
         transform_inverse: Mat4::from_rotation_y(PI / 2.0),
+        ..PatternType::Ring.create()
     });
     world.add_shape(floor);
 
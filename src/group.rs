@@ -0,0 +1,114 @@
+use glam::Mat4;
+use crate::shader_types::{CsgNode, CsgOperation, Material, Shape, ShapeType, World};
+
+/// A tree of shapes that share a transform. The shader only knows about individual shapes,
+/// so World::add_group flattens it by baking every parent's transform into each shape's own.
+/// That's the same matrix the book gets by walking up the parents, so normals still come out right.
+pub struct Group {
+    pub transform: Mat4,
+    /// Used by any children that don't have their own. If this is None too, they look further up the tree.
+    pub material: Option<Material>,
+    pub children: Vec<GroupChild>,
+}
+
+pub enum GroupChild {
+    Shape { shape: Shape, inherit_material: bool },
+    Group(Group),
+    Csg(Csg),
+}
+
+/// Constructive solid geometry. Either side can be a whole tree (including more CSGs).
+/// The children are flattened like any other group but skipped by the BVH. Instead there's one Csg shape that
+/// intersects all of them and filters the hits (see WorldView::intersect_csg).
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Group,
+    pub right: Group,
+}
+
+impl Default for Group {
+    fn default() -> Self {
+        Group {
+            transform: Mat4::IDENTITY,
+            material: None,
+            children: vec![],
+        }
+    }
+}
+
+impl Group {
+    pub fn new(transform: Mat4) -> Group {
+        Group {
+            transform,
+            ..Default::default()
+        }
+    }
+
+    /// The shape keeps its own material.
+    pub fn add_shape(&mut self, shape: Shape) {
+        self.children.push(GroupChild::Shape { shape, inherit_material: false });
+    }
+
+    /// The shape's material gets replaced by the closest one set on a group above it.
+    pub fn add_shape_inheriting_material(&mut self, shape: Shape) {
+        self.children.push(GroupChild::Shape { shape, inherit_material: true });
+    }
+
+    pub fn add_group(&mut self, group: Group) {
+        self.children.push(GroupChild::Group(group));
+    }
+
+    pub fn add_csg(&mut self, csg: Csg) {
+        self.children.push(GroupChild::Csg(csg));
+    }
+
+    /// Adds every shape in the tree to the world, already moved into world space by `parent`.
+    pub(crate) fn flatten(self, world: &mut World, parent: Mat4, material: Option<Material>, in_csg: bool) {
+        let transform = parent * self.transform;
+        let inverse = transform.inverse();
+        let material = self.material.or(material);
+        for child in self.children {
+            match child {
+                GroupChild::Shape { mut shape, inherit_material } => {
+                    shape.transform_inverse *= inverse;
+                    if inherit_material {
+                        if let Some(material) = material {
+                            shape.material = material;
+                        }
+                    }
+                    world.add_shape(shape);
+                }
+                GroupChild::Group(group) => group.flatten(world, transform, material, in_csg),
+                GroupChild::Csg(csg) => csg.flatten(world, transform, material, in_csg),
+            }
+        }
+    }
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Group, right: Group) -> Csg {
+        Csg { operation, left, right }
+    }
+
+    /// Only the outermost CSG gets a shape. Nested ones are just nodes it filters with.
+    fn flatten(self, world: &mut World, parent: Mat4, material: Option<Material>, in_csg: bool) {
+        let first_node = world.csgs.len() as u32;
+        let first_left = world.shapes.len() as u32;
+        self.left.flatten(world, parent, material, true);
+        let first_right = world.shapes.len() as u32;
+        self.right.flatten(world, parent, material, true);
+        let csg_index = world.add_csg_node(CsgNode {
+            operation: self.operation,
+            first_node,
+            first_left,
+            first_right,
+            end: world.shapes.len() as u32,
+        });
+
+        if !in_csg {
+            let mut shape = ShapeType::Csg.create();
+            shape.csg_index = csg_index;
+            world.add_shape(shape);
+        }
+    }
+}
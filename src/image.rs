@@ -0,0 +1,109 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+
+/// An image for the UvImage pattern. Loaded from a PNG or PPM file.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Packed the way the shader's Texture wants them (red in the lowest byte), one row after another from the top.
+    pub texels: Vec<u32>,
+}
+
+impl Image {
+    /// Works out the format from the start of the file rather than the extension.
+    pub fn load(path: &str) -> io::Result<Image> {
+        let data = fs::read(path)?;
+        if data.starts_with(b"\x89PNG") {
+            Image::parse_png(&data)
+        } else {
+            Image::parse_ppm(&data).ok_or_else(|| invalid("not a PNG or PPM file"))
+        }
+    }
+
+    /// Converts anything the png crate can read (palettes, greyscale, 16 bit, etc.) to 8 bit rgb.
+    pub fn parse_png(data: &[u8]) -> io::Result<Image> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| invalid(&e.to_string()))?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer).map_err(|e| invalid(&e.to_string()))?;
+        let channels = info.color_type.samples();
+        let texels = buffer[..info.buffer_size()]
+            .chunks_exact(channels)
+            .map(|pixel| match pixel {
+                [grey] | [grey, _] => pack(*grey, *grey, *grey),
+                [r, g, b, ..] => pack(*r, *g, *b),
+                _ => unreachable!(),
+            })
+            .collect();
+        Ok(Image { width: info.width, height: info.height, texels })
+    }
+
+    /// Plain (P3) or binary (P6). Values are scaled from the file's maximum to 0-255.
+    pub fn parse_ppm(data: &[u8]) -> Option<Image> {
+        let mut header = PpmHeader { data, pos: 0 };
+        let magic = header.token()?;
+        let width = header.number()?;
+        let height = header.number()?;
+        let max = header.number()?;
+        if max == 0 || max > 65535 || width == 0 || height == 0 {
+            return None;
+        }
+        let count = width.checked_mul(height)? as usize;
+        let values: Vec<u32> = match magic {
+            b"P3" => (0..count * 3).map(|_| header.number()).collect::<Option<_>>()?,
+            b"P6" => {
+                // Exactly one whitespace character between the header and the data.
+                let data = data.get(header.pos + 1..)?;
+                if max < 256 {
+                    data.get(..count * 3)?.iter().map(|&v| v as u32).collect()
+                } else {
+                    data.get(..count * 6)?.chunks_exact(2).map(|v| u16::from_be_bytes([v[0], v[1]]) as u32).collect()
+                }
+            }
+            _ => return None,
+        };
+        let scale = |v: u32| ((v.min(max) * 255 + max / 2) / max) as u8;
+        let texels = values.chunks_exact(3).map(|c| pack(scale(c[0]), scale(c[1]), scale(c[2]))).collect();
+        Some(Image { width, height, texels })
+    }
+}
+
+fn pack(r: u8, g: u8, b: u8) -> u32 {
+    r as u32 | (g as u32) << 8 | (b as u32) << 16
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Whitespace separated tokens where # starts a comment that goes to the end of the line.
+struct PpmHeader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PpmHeader<'a> {
+    fn token(&mut self) -> Option<&'a [u8]> {
+        loop {
+            match self.data.get(self.pos)? {
+                b'#' => {
+                    while *self.data.get(self.pos)? != b'\n' {
+                        self.pos += 1;
+                    }
+                }
+                c if c.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        while self.data.get(self.pos).is_some_and(|c| !c.is_ascii_whitespace() && *c != b'#') {
+            self.pos += 1;
+        }
+        Some(&self.data[start..self.pos])
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        std::str::from_utf8(self.token()?).ok()?.parse().ok()
+    }
+}
@@ -1,5 +1,9 @@
+pub mod bvh;
 mod controller;
 pub mod demo;
+pub mod group;
+pub mod image;
+pub mod obj;
 pub mod shader_types;
 pub mod window;
 
@@ -10,4 +14,4 @@ mod bindings;
 
 #[cfg(test)]
 mod rtc_tests;
-mod scene;
+pub mod scene;
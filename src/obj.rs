@@ -0,0 +1,124 @@
+use glam::{vec4, Vec4};
+use crate::group::Group;
+use crate::shader_types::{ShapeType, TriangleData, World};
+
+/// A mesh loaded from a Wavefront OBJ file. Only understands the parts used in the book
+/// (vertices, normals, faces and groups) and counts any other lines instead of failing.
+#[derive(Default)]
+pub struct ObjFile {
+    pub vertices: Vec<Vec4>,
+    pub normals: Vec<Vec4>,
+    /// Faces before the first `g` line go in a group with an empty name.
+    pub groups: Vec<ObjGroup>,
+    pub ignored_lines: usize,
+}
+
+#[derive(Default)]
+pub struct ObjGroup {
+    pub name: String,
+    /// Either Triangle or SmoothTriangle depending on if the face had normals.
+    pub triangles: Vec<(ShapeType, TriangleData)>,
+}
+
+impl ObjFile {
+    pub fn parse(data: &str) -> ObjFile {
+        let mut obj = ObjFile {
+            groups: vec![ObjGroup::default()],
+            ..Default::default()
+        };
+
+        for line in data.lines() {
+            let mut parts = line.split_whitespace();
+            let understood = match parts.next() {
+                Some("v") => parse_vec3(parts, 1.0).map(|v| obj.vertices.push(v)),
+                Some("vn") => parse_vec3(parts, 0.0).map(|v| obj.normals.push(v)),
+                Some("f") => obj.parse_face(parts),
+                Some("g") => {
+                    let name = parts.collect::<Vec<_>>().join(" ");
+                    obj.groups.push(ObjGroup { name, triangles: vec![] });
+                    Some(())
+                }
+                None => Some(()), // blank line
+                _ => None,
+            };
+            if understood.is_none() {
+                obj.ignored_lines += 1;
+            }
+        }
+
+        obj
+    }
+
+    /// A polygon with more than three vertices is split into a fan of triangles that share the first vertex.
+    fn parse_face<'a>(&mut self, parts: impl Iterator<Item = &'a str>) -> Option<()> {
+        let mut vertices = vec![];
+        let mut normals = vec![];
+        for part in parts {
+            // v, v/vt, v//vn or v/vt/vn. Texture coordinates are ignored.
+            let mut indices = part.split('/');
+            vertices.push(lookup(&self.vertices, indices.next()?)?);
+            if let Some(n) = indices.nth(1) {
+                normals.push(lookup(&self.normals, n)?);
+            }
+        }
+        if vertices.len() < 3 {
+            return None;
+        }
+        let smooth = normals.len() == vertices.len();
+
+        let group = self.groups.last_mut()?;
+        for i in 1..(vertices.len() - 1) {
+            let (p1, p2, p3) = (vertices[0], vertices[i], vertices[i + 1]);
+            group.triangles.push(if smooth {
+                let triangle = TriangleData::smooth(p1, p2, p3, normals[0], normals[i], normals[i + 1]);
+                (ShapeType::SmoothTriangle, triangle)
+            } else {
+                (ShapeType::Triangle, TriangleData::flat(p1, p2, p3))
+            });
+        }
+        Some(())
+    }
+
+    pub fn group(&self, name: &str) -> Option<&ObjGroup> {
+        self.groups.iter().find(|g| g.name == name)
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.groups.iter().map(|g| g.triangles.len()).sum()
+    }
+
+    /// Each OBJ group becomes a child group. The triangle data goes straight into the world
+    /// but the shapes aren't added until the returned group is.
+    /// The triangles all inherit their material so one set on the group (or above it) applies to the whole mesh.
+    pub fn to_group(&self, world: &mut World) -> Group {
+        let mut result = Group::default();
+        for group in self.groups.iter().filter(|g| !g.triangles.is_empty()) {
+            let mut child = Group::default();
+            for (shape_type, triangle) in &group.triangles {
+                let mut shape = shape_type.create();
+                shape.triangle_index = world.add_triangle(*triangle);
+                child.add_shape_inheriting_material(shape);
+            }
+            result.add_group(child);
+        }
+        result
+    }
+}
+
+fn parse_vec3<'a>(mut parts: impl Iterator<Item = &'a str>, w: f32) -> Option<Vec4> {
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    Some(vec4(x, y, z, w))
+}
+
+/// OBJ indices start at 1 and negative ones count back from the most recent.
+fn lookup(data: &[Vec4], index: &str) -> Option<Vec4> {
+    let index: i64 = index.parse().ok()?;
+    let i = if index < 0 {
+        data.len() as i64 + index
+    } else {
+        index - 1
+    };
+    data.get(usize::try_from(i).ok()?).copied()
+}
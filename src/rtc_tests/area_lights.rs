@@ -0,0 +1,171 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::rtc_tests::{assert_approx, default_test_world, point, vector};
+use crate::shader_types::{Light, Random, ShapeType, World};
+use glam::{vec3a, Vec3A, Vec4};
+
+fn rng() -> Random {
+    Random { state: 0 }
+}
+
+fn assert_colour(a: Vec3A, b: Vec3A) {
+    assert_approx(a.x, b.x);
+    assert_approx(a.y, b.y);
+    assert_approx(a.z, b.z);
+}
+
+#[test]
+fn is_shadowed_tests_for_occlusion_between_two_points() {
+    let world = default_test_world();
+    let light_position = point(-10.0, -10.0, -10.0);
+    let light = Light::point(light_position, vec3a(1.0, 1.0, 1.0));
+    let cases = [
+        (point(-10.0, -10.0, 10.0), false),
+        (point(10.0, 10.0, 10.0), true),
+        (point(-20.0, -20.0, -20.0), false),
+        (point(-5.0, -5.0, -5.0), false),
+    ];
+    for (p, result) in cases {
+        let expected = if result { Vec3A::ZERO } else { Vec3A::ONE };
+        assert_eq!(unsafe { world.view().transmittance(&light, &light_position, &p) }, expected);
+    }
+}
+
+#[test]
+fn point_lights_evaluate_the_light_intensity_at_a_given_point() {
+    let world = default_test_world();
+    let light = world.lights[0];
+    let cases = [
+        (point(0.0, 1.0001, 0.0), 1.0),
+        (point(-1.0001, 0.0, 0.0), 1.0),
+        (point(0.0, 0.0, -1.0001), 1.0),
+        (point(0.0, 0.0, 1.0001), 0.0),
+        (point(1.0001, 0.0, 0.0), 0.0),
+        (point(0.0, -1.0001, 0.0), 0.0),
+        (point(0.0, 0.0, 0.0), 0.0),
+    ];
+    for (p, result) in cases {
+        assert_eq!(unsafe { world.view().intensity_at(&light, p, &mut rng()) }, Vec3A::splat(result));
+    }
+}
+
+#[test]
+fn lighting_uses_light_intensity_to_attenuate_colour() {
+    let mut world = default_test_world();
+    world.lights[0] = Light::point(point(0.0, 0.0, -10.0), vec3a(1.0, 1.0, 1.0));
+    let mut material = world.shapes[0].material;
+    material.ambient = 0.1;
+    material.diffuse = 0.9;
+    material.specular = 0.0;
+    material.colour = vec3a(1.0, 1.0, 1.0);
+    let p = point(0.0, 0.0, -1.0);
+    let eyev = vector(0.0, 0.0, -1.0);
+    let normalv = vector(0.0, 0.0, -1.0);
+    let cases = [(1.0, 1.0), (0.5, 0.55), (0.0, 0.1)];
+    for (intensity, result) in cases {
+        let colour = unsafe { material.lighting(material.colour, world.lights[0], p, eyev, normalv, Vec3A::splat(intensity), &mut rng()) };
+        assert_colour(colour, Vec3A::splat(result));
+    }
+}
+
+#[test]
+fn creating_an_area_light() {
+    let light = Light::area(
+        point(0.0, 0.0, 0.0),
+        vector(2.0, 0.0, 0.0),
+        4,
+        vector(0.0, 0.0, 1.0),
+        2,
+        vec3a(1.0, 1.0, 1.0),
+    );
+    assert_eq!(light.corner, point(0.0, 0.0, 0.0));
+    assert_eq!(light.uvec, vector(0.5, 0.0, 0.0));
+    assert_eq!(light.usteps, 4);
+    assert_eq!(light.vvec, vector(0.0, 0.0, 0.5));
+    assert_eq!(light.vsteps, 2);
+    assert_eq!(light.samples(), 8);
+    assert_eq!(light.position, point(1.0, 0.0, 0.5));
+}
+
+#[test]
+fn finding_a_single_point_on_an_area_light() {
+    let light = Light::area(
+        point(0.0, 0.0, 0.0),
+        vector(2.0, 0.0, 0.0),
+        4,
+        vector(0.0, 0.0, 1.0),
+        2,
+        vec3a(1.0, 1.0, 1.0),
+    );
+    let cases = [
+        (0, 0, point(0.25, 0.0, 0.25)),
+        (1, 0, point(0.75, 0.0, 0.25)),
+        (0, 1, point(0.25, 0.0, 0.75)),
+        (2, 0, point(1.25, 0.0, 0.25)),
+        (3, 1, point(1.75, 0.0, 0.75)),
+    ];
+    for (u, v, result) in cases {
+        assert_eq!(unsafe { light.point_on(u, v, &mut rng()) }, result);
+    }
+}
+
+fn book_area_light() -> Light {
+    Light::area(
+        point(-0.5, -0.5, -5.0),
+        vector(1.0, 0.0, 0.0),
+        2,
+        vector(0.0, 1.0, 0.0),
+        2,
+        vec3a(1.0, 1.0, 1.0),
+    )
+}
+
+#[test]
+fn the_area_light_intensity_function() {
+    let world = default_test_world();
+    let light = book_area_light();
+    let cases = [
+        (point(0.0, 0.0, 2.0), 0.0),
+        (point(1.0, -1.0, 2.0), 0.25),
+        (point(1.5, 0.0, 2.0), 0.5),
+        (point(1.25, 1.25, 3.0), 0.75),
+        (point(0.0, 0.0, -2.0), 1.0),
+    ];
+    for (p, result) in cases {
+        assert_eq!(unsafe { world.view().intensity_at(&light, p, &mut rng()) }, Vec3A::splat(result));
+    }
+}
+
+#[test]
+fn jittered_samples_stay_in_their_cell() {
+    let mut light = book_area_light();
+    light.jitter = true;
+    let mut rng = rng();
+    for _ in 0..100 {
+        let p = unsafe { light.point_on(1, 0, &mut rng) };
+        assert!((0.0..=0.5).contains(&p.x) && (-0.5..=0.0).contains(&p.y), "{}", p);
+        assert_eq!(p.z, -5.0);
+    }
+}
+
+#[test]
+fn lighting_samples_the_area_light() {
+    let mut world = World::default();
+    world.add_light(book_area_light());
+    let mut material = ShapeType::Sphere.create().material;
+    material.ambient = 0.1;
+    material.diffuse = 0.9;
+    material.specular = 0.0;
+    material.colour = vec3a(1.0, 1.0, 1.0);
+    let eye = point(0.0, 0.0, -5.0);
+    let cases = [
+        (point(0.0, 0.0, -1.0), 0.9965),
+        (point(0.0, FRAC_1_SQRT_2, -FRAC_1_SQRT_2), 0.6232),
+    ];
+    for (p, result) in cases {
+        let eyev = (eye - p).normalize();
+        let normalv = Vec4::new(p.x, p.y, p.z, 0.0);
+        let colour = unsafe { material.lighting(material.colour, world.lights[0], p, eyev, normalv, Vec3A::ONE, &mut rng()) };
+        assert_colour(colour, Vec3A::splat(result));
+    }
+}
@@ -0,0 +1,78 @@
+use std::f32::consts::{PI, SQRT_2};
+
+use crate::bvh::{world_bounds, Bounds};
+use crate::rtc_tests::{assert_approx, point};
+use crate::shader_types::{ShapeType, TriangleData};
+use glam::{vec3, Mat4, Vec3};
+
+fn assert_bounds(bounds: Bounds, min: Vec3, max: Vec3) {
+    for i in 0..3 {
+        assert_approx(bounds.min[i], min[i]);
+        assert_approx(bounds.max[i], max[i]);
+    }
+}
+
+#[test]
+fn a_sphere_has_a_bounding_box() {
+    let shape = ShapeType::Sphere.create();
+    assert_bounds(world_bounds(&shape, &[]), vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn a_plane_is_unbounded() {
+    let shape = ShapeType::Plane.create();
+    assert_eq!(world_bounds(&shape, &[]), Bounds::EVERYTHING);
+}
+
+#[test]
+fn a_cube_has_a_bounding_box() {
+    let shape = ShapeType::Cube.create();
+    assert_bounds(world_bounds(&shape, &[]), vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn an_unbounded_cylinder_is_unbounded() {
+    let shape = ShapeType::Cylinder.create();
+    assert_eq!(world_bounds(&shape, &[]), Bounds::EVERYTHING);
+}
+
+#[test]
+fn a_bounded_cylinder_has_a_bounding_box() {
+    let mut shape = ShapeType::Cylinder.create();
+    shape.minimum = -5.0;
+    shape.maximum = 3.0;
+    assert_bounds(world_bounds(&shape, &[]), vec3(-1.0, -5.0, -1.0), vec3(1.0, 3.0, 1.0));
+}
+
+#[test]
+fn a_bounded_cone_has_a_bounding_box() {
+    let mut shape = ShapeType::Cone.create();
+    shape.minimum = -5.0;
+    shape.maximum = 3.0;
+    assert_bounds(world_bounds(&shape, &[]), vec3(-5.0, -5.0, -5.0), vec3(5.0, 3.0, 5.0));
+}
+
+#[test]
+fn a_triangle_has_a_bounding_box() {
+    let triangles = [TriangleData::flat(point(-3.0, 7.0, 2.0), point(6.0, 2.0, -4.0), point(2.0, -1.0, -1.0))];
+    let shape = ShapeType::Triangle.create();
+    assert_bounds(world_bounds(&shape, &triangles), vec3(-3.0, -1.0, -4.0), vec3(6.0, 7.0, 2.0));
+}
+
+#[test]
+fn bounds_are_in_world_space() {
+    let mut shape = ShapeType::Sphere.create();
+    shape.set_transform(Mat4::from_translation(vec3(1.0, -3.0, 5.0)) * Mat4::from_scale(vec3(0.5, 2.0, 4.0)));
+    assert_bounds(world_bounds(&shape, &[]), vec3(0.5, -5.0, 1.0), vec3(1.5, -1.0, 9.0));
+}
+
+#[test]
+fn rotating_a_shape_grows_its_bounding_box() {
+    let mut shape = ShapeType::Cube.create();
+    shape.set_transform(Mat4::from_rotation_x(PI / 4.0) * Mat4::from_rotation_y(PI / 4.0));
+    assert_bounds(
+        world_bounds(&shape, &[]),
+        vec3(-SQRT_2, -1.7071, -1.7071),
+        vec3(SQRT_2, 1.7071, 1.7071),
+    );
+}
@@ -0,0 +1,61 @@
+use crate::rtc_tests::{assert_approx, hits_on, normal_on, point, vector};
+use crate::shader_types::{Ray, ShapeType};
+use glam::Vec4;
+
+fn ray(origin: Vec4, direction: Vec4) -> Ray {
+    Ray::new(origin, direction.normalize())
+}
+
+#[test]
+fn intersecting_a_cone_with_a_ray() {
+    let shape = ShapeType::Cone.create();
+    for (origin, direction, t0, t1) in [
+        (point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 5.0, 5.0),
+        (point(0.0, 0.0, -5.0), vector(1.0, 1.0, 1.0), 8.66025, 8.66025),
+        (point(1.0, 1.0, -5.0), vector(-0.5, -1.0, 1.0), 4.55006, 49.44994),
+    ] {
+        let hits = hits_on(&shape, ray(origin, direction));
+        assert_eq!(hits.len(), 2);
+        // Precision is worse with f32 when the ray grazes the cone.
+        assert!((hits[0] - t0).abs() < 0.01);
+        assert!((hits[1] - t1).abs() < 0.01);
+    }
+}
+
+#[test]
+fn intersecting_a_cone_with_a_ray_parallel_to_one_of_its_halves() {
+    let shape = ShapeType::Cone.create();
+    let hits = hits_on(&shape, ray(point(0.0, 0.0, -1.0), vector(0.0, 1.0, 1.0)));
+    assert_eq!(hits.len(), 1);
+    assert_approx(hits[0], 0.35355);
+}
+
+#[test]
+fn intersecting_a_cones_end_caps() {
+    let mut shape = ShapeType::Cone.create();
+    shape.minimum = -0.5;
+    shape.maximum = 0.5;
+    shape.closed = true;
+    for (origin, direction, count) in [
+        (point(0.0, 0.0, -5.0), vector(0.0, 1.0, 0.0), 0),
+        (point(0.0, 0.0, -0.25), vector(0.0, 1.0, 1.0), 2),
+        (point(0.0, 0.0, -0.25), vector(0.0, 1.0, 0.0), 4),
+    ] {
+        assert_eq!(hits_on(&shape, ray(origin, direction)).len(), count);
+    }
+}
+
+#[test]
+fn computing_the_normal_vector_on_a_cone() {
+    let shape = ShapeType::Cone.create();
+    for (p, normal) in [
+        (point(1.0, 1.0, 1.0), vector(1.0, -(2.0f32.sqrt()), 1.0)),
+        (point(-1.0, -1.0, 0.0), vector(-1.0, 1.0, 0.0)),
+    ] {
+        let n = normal_on(&shape, p);
+        let expected = normal.normalize();
+        assert_approx(n.x, expected.x);
+        assert_approx(n.y, expected.y);
+        assert_approx(n.z, expected.z);
+    }
+}
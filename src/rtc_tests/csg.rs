@@ -0,0 +1,136 @@
+use crate::group::{Csg, Group};
+use crate::rtc_tests::{point, vector};
+use crate::shader_types::{CsgNode, CsgOperation, Intersection, Intersections, Ray, ShapeType, World};
+use glam::{vec3, Mat4};
+
+fn node(operation: CsgOperation) -> CsgNode {
+    CsgNode {
+        operation,
+        first_node: 0,
+        first_left: 0,
+        first_right: 1,
+        end: 2,
+    }
+}
+
+#[test]
+fn evaluating_the_rule_for_a_csg_operation() {
+    use CsgOperation::*;
+    // op, lhit, inl, inr, result
+    let cases = [
+        (CsgUnion, true, true, true, false),
+        (CsgUnion, true, true, false, true),
+        (CsgUnion, true, false, true, false),
+        (CsgUnion, true, false, false, true),
+        (CsgUnion, false, true, true, false),
+        (CsgUnion, false, true, false, false),
+        (CsgUnion, false, false, true, true),
+        (CsgUnion, false, false, false, true),
+        (CsgIntersection, true, true, true, true),
+        (CsgIntersection, true, true, false, false),
+        (CsgIntersection, true, false, true, true),
+        (CsgIntersection, true, false, false, false),
+        (CsgIntersection, false, true, true, true),
+        (CsgIntersection, false, true, false, true),
+        (CsgIntersection, false, false, true, false),
+        (CsgIntersection, false, false, false, false),
+        (CsgDifference, true, true, true, false),
+        (CsgDifference, true, true, false, true),
+        (CsgDifference, true, false, true, false),
+        (CsgDifference, true, false, false, true),
+        (CsgDifference, false, true, true, true),
+        (CsgDifference, false, true, false, true),
+        (CsgDifference, false, false, true, false),
+        (CsgDifference, false, false, false, false),
+    ];
+    for (op, lhit, inl, inr, result) in cases {
+        assert_eq!(unsafe { node(op).allows(lhit, inl, inr) }, result, "{:?} {} {} {}", op, lhit, inl, inr);
+    }
+}
+
+#[test]
+fn filtering_a_list_of_intersections() {
+    // s1 is shape 0 on the left and s2 is shape 1 on the right.
+    let cases = [
+        (CsgOperation::CsgUnion, 0, 3),
+        (CsgOperation::CsgIntersection, 1, 2),
+        (CsgOperation::CsgDifference, 0, 1),
+    ];
+    for (op, x0, x1) in cases {
+        let mut xs = Intersections::default();
+        let all = [Intersection::new(1.0, 0), Intersection::new(2.0, 1), Intersection::new(3.0, 0), Intersection::new(4.0, 1)];
+        for hit in all {
+            unsafe { xs.add(hit.t, hit.obj, 0.0, 0.0) };
+        }
+        unsafe { xs.filter_csg(&node(op)) };
+        assert_eq!(xs.count, 2);
+        assert_eq!(xs.hits[0].t, all[x0].t);
+        assert_eq!(xs.hits[1].t, all[x1].t);
+    }
+}
+
+#[test]
+fn hits_outside_the_csg_are_kept() {
+    let mut xs = Intersections::default();
+    for hit in [Intersection::new(1.0, 0), Intersection::new(2.0, 1), Intersection::new(3.0, 2), Intersection::new(4.0, 0), Intersection::new(5.0, 1)] {
+        unsafe { xs.add(hit.t, hit.obj, 0.0, 0.0) };
+    }
+    unsafe { xs.filter_csg(&node(CsgOperation::CsgIntersection)) };
+    let ts: Vec<_> = xs.hits[..xs.count as usize].iter().map(|h| h.t).collect();
+    assert_eq!(ts, vec![2.0, 3.0, 4.0]);
+}
+
+fn csg_world(operation: CsgOperation) -> World {
+    let mut left = Group::default();
+    left.add_shape(ShapeType::Sphere.create());
+    let mut right = Group::default();
+    let mut s2 = ShapeType::Sphere.create();
+    s2.set_transform(Mat4::from_translation(vec3(0.0, 0.0, 0.5)));
+    right.add_shape(s2);
+    let mut world = World::default();
+    world.add_csg(Csg::new(operation, left, right));
+    world
+}
+
+fn world_hits(world: &World, ray: Ray) -> Vec<(f32, u32)> {
+    let mut xs = Intersections::default();
+    unsafe { world.view().intersect(&ray, &mut xs) };
+    xs.hits[..xs.count as usize].iter().map(|h| (h.t, h.obj)).collect()
+}
+
+#[test]
+fn a_ray_misses_a_csg_object() {
+    let world = csg_world(CsgOperation::CsgUnion);
+    let ray = Ray::new(point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0));
+    assert!(world_hits(&world, ray).is_empty());
+}
+
+#[test]
+fn a_ray_hits_a_csg_object() {
+    let world = csg_world(CsgOperation::CsgUnion);
+    let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+    assert_eq!(world_hits(&world, ray), vec![(4.0, 0), (6.5, 1)]);
+}
+
+#[test]
+fn csgs_can_be_nested() {
+    // (s1 - s2) - s3 where s3 removes the front of s1 that s2 didn't.
+    let mut inner = Group::default();
+    inner.add_csg(Csg::new(CsgOperation::CsgDifference, single_sphere(0.0), single_sphere(-1.5)));
+    let mut world = World::default();
+    world.add_csg(Csg::new(CsgOperation::CsgDifference, inner, single_sphere(1.5)));
+    assert_eq!(world.shapes.len(), 4);
+    assert_eq!(world.csgs.len(), 2);
+
+    // The only solid part left is between the two cut outs, from z=-0.5 to z=0.5.
+    let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+    assert_eq!(world_hits(&world, ray), vec![(4.5, 1), (5.5, 2)]);
+}
+
+fn single_sphere(z: f32) -> Group {
+    let mut group = Group::default();
+    let mut s = ShapeType::Sphere.create();
+    s.set_transform(Mat4::from_translation(vec3(0.0, 0.0, z)));
+    group.add_shape(s);
+    group
+}
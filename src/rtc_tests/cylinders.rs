@@ -0,0 +1,107 @@
+use crate::rtc_tests::{assert_approx, hits_on, normal_on, point, vector};
+use crate::shader_types::{Ray, Shape, ShapeType};
+use glam::Vec4;
+
+fn ray(origin: Vec4, direction: Vec4) -> Ray {
+    Ray::new(origin, direction.normalize())
+}
+
+fn constrained_cylinder() -> Shape {
+    let mut cyl = ShapeType::Cylinder.create();
+    cyl.minimum = 1.0;
+    cyl.maximum = 2.0;
+    cyl
+}
+
+#[test]
+fn a_ray_misses_a_cylinder() {
+    let cyl = ShapeType::Cylinder.create();
+    for (origin, direction) in [
+        (point(1.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)),
+        (point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0)),
+        (point(0.0, 0.0, -5.0), vector(1.0, 1.0, 1.0)),
+    ] {
+        assert!(hits_on(&cyl, ray(origin, direction)).is_empty());
+    }
+}
+
+#[test]
+fn a_ray_strikes_a_cylinder() {
+    let cyl = ShapeType::Cylinder.create();
+    for (origin, direction, t0, t1) in [
+        (point(1.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 5.0, 5.0),
+        (point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 4.0, 6.0),
+        (point(0.5, 0.0, -5.0), vector(0.1, 1.0, 1.0), 6.80798, 7.08872),
+    ] {
+        let hits = hits_on(&cyl, ray(origin, direction));
+        assert_eq!(hits.len(), 2);
+        assert_approx(hits[0], t0);
+        assert_approx(hits[1], t1);
+    }
+}
+
+#[test]
+fn normal_vector_on_a_cylinder() {
+    let cyl = ShapeType::Cylinder.create();
+    for (p, normal) in [
+        (point(1.0, 0.0, 0.0), vector(1.0, 0.0, 0.0)),
+        (point(0.0, 5.0, -1.0), vector(0.0, 0.0, -1.0)),
+        (point(0.0, -2.0, 1.0), vector(0.0, 0.0, 1.0)),
+        (point(-1.0, 1.0, 0.0), vector(-1.0, 0.0, 0.0)),
+    ] {
+        assert_eq!(normal_on(&cyl, p), normal);
+    }
+}
+
+#[test]
+fn the_default_closed_value_for_a_cylinder() {
+    let cyl = ShapeType::Cylinder.create();
+    assert!(!cyl.closed);
+    assert!(cyl.minimum < -1e30 && cyl.maximum > 1e30);
+}
+
+#[test]
+fn intersecting_a_constrained_cylinder() {
+    let cyl = constrained_cylinder();
+    for (origin, direction, count) in [
+        (point(0.0, 1.5, 0.0), vector(0.1, 1.0, 0.0), 0),
+        (point(0.0, 3.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+        (point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+        (point(0.0, 2.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+        (point(0.0, 1.0, -5.0), vector(0.0, 0.0, 1.0), 0),
+        (point(0.0, 1.5, -2.0), vector(0.0, 0.0, 1.0), 2),
+    ] {
+        assert_eq!(hits_on(&cyl, ray(origin, direction)).len(), count);
+    }
+}
+
+#[test]
+fn intersecting_the_caps_of_a_closed_cylinder() {
+    let mut cyl = constrained_cylinder();
+    cyl.closed = true;
+    for (origin, direction) in [
+        (point(0.0, 3.0, 0.0), vector(0.0, -1.0, 0.0)),
+        (point(0.0, 3.0, -2.0), vector(0.0, -1.0, 2.0)),
+        (point(0.0, 4.0, -2.0), vector(0.0, -1.0, 1.0)),
+        (point(0.0, 0.0, -2.0), vector(0.0, 1.0, 2.0)),
+        (point(0.0, -1.0, -2.0), vector(0.0, 1.0, 1.0)),
+    ] {
+        assert_eq!(hits_on(&cyl, ray(origin, direction)).len(), 2);
+    }
+}
+
+#[test]
+fn the_normal_vector_on_a_cylinders_end_caps() {
+    let mut cyl = constrained_cylinder();
+    cyl.closed = true;
+    for (p, normal) in [
+        (point(0.0, 1.0, 0.0), vector(0.0, -1.0, 0.0)),
+        (point(0.5, 1.0, 0.0), vector(0.0, -1.0, 0.0)),
+        (point(0.0, 1.0, 0.5), vector(0.0, -1.0, 0.0)),
+        (point(0.0, 2.0, 0.0), vector(0.0, 1.0, 0.0)),
+        (point(0.5, 2.0, 0.0), vector(0.0, 1.0, 0.0)),
+        (point(0.0, 2.0, 0.5), vector(0.0, 1.0, 0.0)),
+    ] {
+        assert_eq!(normal_on(&cyl, p), normal);
+    }
+}
@@ -0,0 +1,106 @@
+use std::f32::consts::PI;
+
+use crate::group::Group;
+use crate::rtc_tests::{assert_approx, point, vector};
+use crate::shader_types::{Intersections, Material, Ray, ShapeType, World};
+use glam::{vec3, vec3a, Mat4, Vec3};
+
+#[test]
+fn intersecting_a_ray_with_an_empty_group() {
+    let mut world = World::default();
+    world.add_group(Group::default());
+    let ray = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 0.0, 1.0));
+    let mut hits = Intersections::default();
+    unsafe { world.view().intersect(&ray, &mut hits) };
+    assert_eq!(hits.count, 0);
+}
+
+#[test]
+fn intersecting_a_ray_with_a_nonempty_group() {
+    let mut group = Group::default();
+    group.add_shape(ShapeType::Sphere.create());
+    let mut s2 = ShapeType::Sphere.create();
+    s2.set_transform(Mat4::from_translation(vec3(0.0, 0.0, -3.0)));
+    group.add_shape(s2);
+    let mut s3 = ShapeType::Sphere.create();
+    s3.set_transform(Mat4::from_translation(vec3(5.0, 0.0, 0.0)));
+    group.add_shape(s3);
+    let mut world = World::default();
+    world.add_group(group);
+
+    let ray = Ray::new(point(0.0, 0.0, -5.0), vector(0.0, 0.0, 1.0));
+    let mut hits = Intersections::default();
+    unsafe { world.view().intersect(&ray, &mut hits) };
+    let mut objects: Vec<_> = hits.hits[..hits.count as usize].iter().map(|h| (h.t, h.obj)).collect();
+    objects.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let objects: Vec<_> = objects.iter().map(|h| h.1).collect();
+    assert_eq!(objects, vec![1, 1, 0, 0]);
+}
+
+#[test]
+fn intersecting_a_transformed_group() {
+    let mut group = Group::new(Mat4::from_scale(vec3(2.0, 2.0, 2.0)));
+    let mut s = ShapeType::Sphere.create();
+    s.set_transform(Mat4::from_translation(vec3(5.0, 0.0, 0.0)));
+    group.add_shape(s);
+    let mut world = World::default();
+    world.add_group(group);
+
+    let ray = Ray::new(point(10.0, 0.0, -10.0), vector(0.0, 0.0, 1.0));
+    let mut hits = Intersections::default();
+    unsafe { world.view().intersect(&ray, &mut hits) };
+    assert_eq!(hits.count, 2);
+}
+
+/// g1 (rotated) contains g2 (scaled) which contains a translated sphere.
+fn nested_world(scale: Vec3) -> World {
+    let mut g1 = Group::new(Mat4::from_rotation_y(PI / 2.0));
+    let mut g2 = Group::new(Mat4::from_scale(scale));
+    let mut s = ShapeType::Sphere.create();
+    s.set_transform(Mat4::from_translation(vec3(5.0, 0.0, 0.0)));
+    g2.add_shape(s);
+    g1.add_group(g2);
+    let mut world = World::default();
+    world.add_group(g1);
+    world
+}
+
+#[test]
+fn converting_a_point_from_world_to_object_space() {
+    let world = nested_world(vec3(2.0, 2.0, 2.0));
+    let p = world.shapes[0].transform_inverse * point(-2.0, 0.0, -10.0);
+    assert_approx(p.x, 0.0);
+    assert_approx(p.y, 0.0);
+    assert_approx(p.z, -1.0);
+}
+
+#[test]
+fn finding_the_normal_on_a_child_object() {
+    let world = nested_world(vec3(1.0, 2.0, 3.0));
+    let n = unsafe { world.shapes[0].normal_at(point(1.7321, 1.1547, -5.5774), &Default::default(), std::ptr::null()) };
+    assert_approx(n.x, 0.2857);
+    assert_approx(n.y, 0.4286);
+    assert_approx(n.z, -0.8571);
+}
+
+#[test]
+fn children_inherit_the_closest_material() {
+    let red = Material { colour: vec3a(1.0, 0.0, 0.0), ..Default::default() };
+    let blue = Material { colour: vec3a(0.0, 0.0, 1.0), ..Default::default() };
+    let mut own = ShapeType::Sphere.create();
+    own.material.diffuse = 0.5;
+
+    let mut inner = Group { material: Some(blue), ..Default::default() };
+    inner.add_shape_inheriting_material(ShapeType::Sphere.create());
+    let mut outer = Group { material: Some(red), ..Default::default() };
+    outer.add_shape_inheriting_material(ShapeType::Sphere.create());
+    outer.add_shape(own);
+    outer.add_group(inner);
+    let mut world = World::default();
+    world.add_group(outer);
+
+    assert_eq!(world.shapes[0].material.colour, red.colour);
+    assert_eq!(world.shapes[1].material.diffuse, 0.5);
+    assert_eq!(world.shapes[1].material.colour, vec3a(1.0, 1.0, 1.0));
+    assert_eq!(world.shapes[2].material.colour, blue.colour);
+}
@@ -1,5 +1,8 @@
-use crate::rtc_tests::{point, vector};
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::rtc_tests::{assert_approx, glass_sphere, point, vector};
 use crate::shader_types::{Intersection, Intersections, Ray, ShapeType, World};
+use glam::{vec3, Mat4};
 
 #[test]
 fn precomputing_the_state_of_an_intersection() {
@@ -14,3 +17,68 @@ fn precomputing_the_state_of_an_intersection() {
     assert_eq!(comps.eyev, vector(0.0, 0.0, -1.0));
     assert_eq!(comps.normalv, vector(0.0, 0.0, -1.0));
 }
+
+#[test]
+fn finding_n1_and_n2_at_various_intersections() {
+    let mut w = World::default();
+    let mut a = glass_sphere();
+    a.set_transform(Mat4::from_scale(vec3(2.0, 2.0, 2.0)));
+    a.material.refractive_index = 1.5;
+    w.add_shape(a);
+    let mut b = glass_sphere();
+    b.set_transform(Mat4::from_translation(vec3(0.0, 0.0, -0.25)));
+    b.material.refractive_index = 2.0;
+    w.add_shape(b);
+    let mut c = glass_sphere();
+    c.set_transform(Mat4::from_translation(vec3(0.0, 0.0, 0.25)));
+    c.material.refractive_index = 2.5;
+    w.add_shape(c);
+    let r = Ray::new(point(0.0, 0.0, -4.0), vector(0.0, 0.0, 1.0));
+    let mut xs = Intersections::default();
+    for (t, obj) in [(2.0, 0), (2.75, 1), (3.25, 2), (4.75, 1), (5.25, 2), (6.0, 0)] {
+        unsafe { xs.add(t, obj, 0.0, 0.0) };
+    }
+
+    let expected = [(1.0, 1.5), (1.5, 2.0), (2.0, 2.5), (2.5, 2.5), (2.5, 1.5), (1.5, 1.0)];
+    for (i, (n1, n2)) in expected.into_iter().enumerate() {
+        let comps = unsafe { w.view().prepare_comps(&xs.hits[i], &r, &xs) };
+        assert_eq!((comps.n1, comps.n2), (n1, n2), "index {}", i);
+    }
+}
+
+/// The hits are all on shape 0.
+fn intersections(ts: &[f32]) -> Intersections {
+    let mut xs = Intersections::default();
+    for t in ts {
+        unsafe { xs.add(*t, 0, 0.0, 0.0) };
+    }
+    xs
+}
+
+fn schlick_on_glass_sphere(ray: Ray, ts: &[f32], hit: usize) -> f32 {
+    let mut w = World::default();
+    w.add_shape(glass_sphere());
+    let xs = intersections(ts);
+    unsafe { w.view().prepare_comps(&xs.hits[hit], &ray, &xs).schlick() }
+}
+
+#[test]
+fn the_schlick_approximation_under_total_internal_reflection() {
+    let r = Ray::new(point(0.0, 0.0, FRAC_1_SQRT_2), vector(0.0, 1.0, 0.0));
+    let reflectance = schlick_on_glass_sphere(r, &[-FRAC_1_SQRT_2, FRAC_1_SQRT_2], 1);
+    assert_eq!(reflectance, 1.0);
+}
+
+#[test]
+fn the_schlick_approximation_with_a_perpendicular_viewing_angle() {
+    let r = Ray::new(point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+    let reflectance = schlick_on_glass_sphere(r, &[-1.0, 1.0], 1);
+    assert_approx(reflectance, 0.04);
+}
+
+#[test]
+fn the_schlick_approximation_with_small_angle_and_n2_greater_than_n1() {
+    let r = Ray::new(point(0.0, 0.99, -2.0), vector(0.0, 0.0, 1.0));
+    let reflectance = schlick_on_glass_sphere(r, &[1.8589], 0);
+    assert_approx(reflectance, 0.48873);
+}
@@ -0,0 +1,117 @@
+use crate::shader_types::{Light, Material, Random, Ray, ShapeType, World};
+use glam::{vec3, vec3a, vec4, Mat4, Vec3A, Vec4};
+
+/// A unit sphere at the origin with the light coming from above.
+fn lit_from_above(light: Light) -> World {
+    let mut world = World::default();
+    world.add_shape(ShapeType::Sphere.create());
+    world.add_light(light);
+    world
+}
+
+fn brightness(world: &World, point: Vec4) -> f32 {
+    let material = Material {
+        specular: 0.0,
+        ..world.shapes[0].material
+    };
+    let normal = vec4(0.0, 1.0, 0.0, 0.0);
+    let colour = unsafe { material.lighting(vec3a(1.0, 1.0, 1.0), world.lights[0], point, normal, normal, Vec3A::ONE, &mut Random { state: 0 }) };
+    colour.x
+}
+
+#[test]
+fn directional_lights_cast_shadows_from_infinitely_far_away() {
+    let world = lit_from_above(Light::directional(vec4(0.0, -1.0, 0.0, 0.0), vec3a(1.0, 1.0, 1.0)));
+    let intensity = |p| unsafe { world.view().intensity_at(&world.lights[0], p, &mut Random { state: 0 }) };
+    assert_eq!(intensity(vec4(0.0, -1000.0, 0.0, 1.0)), Vec3A::ZERO);
+    assert_eq!(intensity(vec4(2.0, -1000.0, 0.0, 1.0)), Vec3A::ONE);
+    assert_eq!(intensity(vec4(0.0, 1.5, 0.0, 1.0)), Vec3A::ONE);
+}
+
+#[test]
+fn directional_lights_are_the_same_brightness_everywhere() {
+    let world = lit_from_above(Light::directional(vec4(0.0, -1.0, 0.0, 0.0), vec3a(1.0, 1.0, 1.0)));
+    let near = brightness(&world, vec4(0.0, -2.0, 0.0, 1.0));
+    let far = brightness(&world, vec4(500.0, -2.0, 80.0, 1.0));
+    assert_eq!(near, far);
+}
+
+#[test]
+fn spot_lights_fade_out_between_the_cone_angles() {
+    let light = Light::spot(vec4(0.0, 10.0, 0.0, 1.0), vec4(0.0, -1.0, 0.0, 0.0), 0.2, 0.4, vec3a(1.0, 1.0, 1.0));
+    let world = lit_from_above(light);
+    let ambient = world.shapes[0].material.ambient;
+    let at_angle = |angle: f32| brightness(&world, vec4(10.0 * angle.tan(), 0.0, 0.0, 1.0));
+
+    let centre = brightness(&world, vec4(0.0, 0.0, 0.0, 1.0));
+    let inside = at_angle(0.19);
+    let edge = at_angle(0.3);
+    let outside = at_angle(0.41);
+    assert!(centre > inside && inside > edge && edge > outside);
+    // Inside the inner cone is only dimmer because the light hits at more of an angle.
+    assert!((inside - ambient) / (centre - ambient) > 0.9);
+    assert_eq!(outside, ambient);
+}
+
+#[test]
+fn attenuated_lights_get_dimmer_with_distance() {
+    let mut light = Light::point(vec4(0.0, 10.0, 0.0, 1.0), vec3a(1.0, 1.0, 1.0));
+    light.attenuation_constant = 0.5;
+    light.attenuation_linear = 0.1;
+    light.attenuation_quadratic = 0.01;
+    assert_eq!(unsafe { light.attenuation(light.position, vec4(0.0, 0.0, 0.0, 1.0)) }, 1.0 / 2.5);
+
+    let world = lit_from_above(light);
+    let ambient = world.shapes[0].material.ambient;
+    let near = brightness(&world, vec4(0.0, 5.0, 0.0, 1.0)) - ambient;
+    let far = brightness(&world, vec4(0.0, -5.0, 0.0, 1.0)) - ambient;
+    assert!(near > far * 3.0);
+}
+
+#[test]
+fn lights_without_shadows_shine_through_everything() {
+    let mut light = Light::point(vec4(0.0, 10.0, 0.0, 1.0), vec3a(1.0, 1.0, 1.0));
+    let below = vec4(0.0, -5.0, 0.0, 1.0);
+    let world = lit_from_above(light);
+    assert_eq!(unsafe { world.view().intensity_at(&world.lights[0], below, &mut Random { state: 0 }) }, Vec3A::ZERO);
+
+    light.shadows = false;
+    let world = lit_from_above(light);
+    assert_eq!(unsafe { world.view().intensity_at(&world.lights[0], below, &mut Random { state: 0 }) }, Vec3A::ONE);
+}
+
+#[test]
+fn shapes_can_opt_out_of_shadows() {
+    let light = Light::point(vec4(0.0, 10.0, 0.0, 1.0), vec3a(1.0, 1.0, 1.0));
+    let below = vec4(0.0, -5.0, 0.0, 1.0);
+    let mut world = lit_from_above(light);
+    world.shapes[0].casts_shadow = false;
+    assert_eq!(unsafe { world.view().intensity_at(&world.lights[0], below, &mut Random { state: 0 }) }, Vec3A::ONE);
+
+    // A floor in the sphere's shadow gets lit anyway when it doesn't receive shadows.
+    let mut world = lit_from_above(light);
+    let mut floor = ShapeType::Plane.create();
+    floor.set_transform(Mat4::from_translation(vec3(0.0, -5.0, 0.0)));
+    world.add_shape(floor);
+    let ray = Ray {
+        origin: vec4(0.0, 0.0, -5.0, 1.0),
+        direction: (below - vec4(0.0, 0.0, -5.0, 1.0)).normalize(),
+    };
+    let shadowed = unsafe { world.view().colour_at(&ray, &mut Random { state: 0 }) };
+    world.shapes[1].receives_shadow = false;
+    let lit = unsafe { world.view().colour_at(&ray, &mut Random { state: 0 }) };
+    assert!(lit.x > shadowed.x * 2.0, "{} {}", lit, shadowed);
+}
+
+#[test]
+fn transparent_shapes_cast_lighter_tinted_shadows() {
+    let light = Light::point(vec4(0.0, 10.0, 0.0, 1.0), vec3a(1.0, 1.0, 1.0));
+    let below = vec4(0.0, -5.0, 0.0, 1.0);
+    let mut world = lit_from_above(light);
+    world.shapes[0].material.transparency = 0.5;
+    world.shapes[0].material.colour = vec3a(0.0, 0.1, 0.2);
+    // The ray goes in and out of the sphere so 0.5 * 0.5 = 0.25 of the light gets through.
+    // It's tinted twice too, by the colour scaled so its brightest channel is 1.
+    let visible = unsafe { world.view().intensity_at(&world.lights[0], below, &mut Random { state: 0 }) };
+    assert_eq!(visible, vec3a(0.0, 0.0625, 0.25));
+}
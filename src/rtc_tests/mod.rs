@@ -1,9 +1,22 @@
+mod area_lights;
+mod bounds;
+mod cones;
+mod csg;
+mod cylinders;
+mod groups;
 mod intersections;
+mod lights;
+mod obj;
+mod patterns;
 mod rays;
+mod texture_mapping;
+mod triangles;
+mod world;
 
 use crate::bindings::Ray;
-use crate::shader_types::{Intersection, Intersections, PointLight, ShapeType, World, MAX_HITS};
+use crate::shader_types::{Intersection, Intersections, Light, Shape, ShapeType, World, MAX_HITS};
 use glam::{vec3, vec3a, vec4, Mat4, Vec4};
+use std::ptr;
 
 fn default_test_world() -> World {
     let mut world = World::default();
@@ -15,14 +28,18 @@ fn default_test_world() -> World {
     let mut sphere = ShapeType::Sphere.create();
     sphere.set_transform(Mat4::from_scale(vec3(0.5, 0.5, 0.5)));
     world.add_shape(sphere);
-    world.add_light(PointLight {
-        position: vec4(-10.0, 10.0, -10.0, 0.0),
-        intensity: vec3a(1.0, 1.0, 1.0),
-    });
+    world.add_light(Light::point(point(-10.0, 10.0, -10.0), vec3a(1.0, 1.0, 1.0)));
 
     world
 }
 
+fn glass_sphere() -> Shape {
+    let mut s = ShapeType::Sphere.create();
+    s.material.transparency = 1.0;
+    s.material.refractive_index = 1.5;
+    s
+}
+
 fn vector(x: f32, y: f32, z: f32) -> Vec4 {
     vec4(x, y, z, 0.0)
 }
@@ -31,6 +48,22 @@ fn point(x: f32, y: f32, z: f32) -> Vec4 {
     vec4(x, y, z, 1.0)
 }
 
+/// The t values of every intersection, in sorted order.
+fn hits_on(shape: &Shape, ray: Ray) -> Vec<f32> {
+    let mut hits = Intersections::default();
+    unsafe { shape.intersect(&ray, &mut hits, ptr::null()) };
+    hits.hits[..hits.count as usize].iter().map(|hit| hit.t).collect()
+}
+
+/// For shapes that don't care where the ray hit or need any triangle data.
+fn normal_on(shape: &Shape, p: Vec4) -> Vec4 {
+    unsafe { shape.normal_at(p, &Intersection::default(), ptr::null()) }
+}
+
+fn assert_approx(a: f32, b: f32) {
+    assert!((a - b).abs() < 0.0001, "{} != {}", a, b);
+}
+
 impl Default for Intersection {
     fn default() -> Self {
         Intersection::new(0.0, 0)
@@ -41,6 +74,7 @@ impl Default for Intersections {
     fn default() -> Self {
         Intersections {
             count: 0,
+            limit: MAX_HITS as i32,
             is_hit: false,
             hits: [Default::default(); MAX_HITS as usize],
         }
@@ -49,7 +83,7 @@ impl Default for Intersections {
 
 impl Intersection {
     fn new(t: f32, obj: u32) -> Intersection {
-        Intersection { t, obj }
+        Intersection { t, obj, u: 0.0, v: 0.0 }
     }
 }
 
@@ -0,0 +1,153 @@
+use crate::obj::ObjFile;
+use crate::rtc_tests::{point, vector};
+use crate::shader_types::{ShapeType, World};
+
+#[test]
+fn ignoring_unrecognized_lines() {
+    let gibberish = "There was a young lady named Bright
+who traveled much faster than light.
+She set out one day
+in a relative way,
+and came back the previous night.";
+    let obj = ObjFile::parse(gibberish);
+    assert_eq!(obj.ignored_lines, 5);
+}
+
+#[test]
+fn vertex_records() {
+    let file = "v -1 1 0
+v -1.0000 0.5000 0.0000
+v 1 0 0
+v 1 1 0";
+    let obj = ObjFile::parse(file);
+    assert_eq!(obj.vertices[0], point(-1.0, 1.0, 0.0));
+    assert_eq!(obj.vertices[1], point(-1.0, 0.5, 0.0));
+    assert_eq!(obj.vertices[2], point(1.0, 0.0, 0.0));
+    assert_eq!(obj.vertices[3], point(1.0, 1.0, 0.0));
+}
+
+#[test]
+fn parsing_triangle_faces() {
+    let file = "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+f 1 2 3
+f 1 3 4";
+    let obj = ObjFile::parse(file);
+    let g = &obj.groups[0].triangles;
+    let (t1, t2) = (g[0].1, g[1].1);
+    assert_eq!(t1.p1, obj.vertices[0]);
+    assert_eq!(t1.p2, obj.vertices[1]);
+    assert_eq!(t1.p3, obj.vertices[2]);
+    assert_eq!(t2.p1, obj.vertices[0]);
+    assert_eq!(t2.p2, obj.vertices[2]);
+    assert_eq!(t2.p3, obj.vertices[3]);
+}
+
+#[test]
+fn triangulating_polygons() {
+    let file = "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+v 0 2 0
+
+f 1 2 3 4 5";
+    let obj = ObjFile::parse(file);
+    let g = &obj.groups[0].triangles;
+    assert_eq!(g.len(), 3);
+    let (t1, t2, t3) = (g[0].1, g[1].1, g[2].1);
+    assert_eq!(t1.p1, obj.vertices[0]);
+    assert_eq!(t1.p2, obj.vertices[1]);
+    assert_eq!(t1.p3, obj.vertices[2]);
+    assert_eq!(t2.p1, obj.vertices[0]);
+    assert_eq!(t2.p2, obj.vertices[2]);
+    assert_eq!(t2.p3, obj.vertices[3]);
+    assert_eq!(t3.p1, obj.vertices[0]);
+    assert_eq!(t3.p2, obj.vertices[3]);
+    assert_eq!(t3.p3, obj.vertices[4]);
+}
+
+#[test]
+fn triangles_in_groups() {
+    let file = "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+g FirstGroup
+f 1 2 3
+g SecondGroup
+f 1 3 4";
+    let obj = ObjFile::parse(file);
+    let t1 = obj.group("FirstGroup").unwrap().triangles[0].1;
+    let t2 = obj.group("SecondGroup").unwrap().triangles[0].1;
+    assert_eq!(t1.p1, obj.vertices[0]);
+    assert_eq!(t1.p2, obj.vertices[1]);
+    assert_eq!(t1.p3, obj.vertices[2]);
+    assert_eq!(t2.p1, obj.vertices[0]);
+    assert_eq!(t2.p2, obj.vertices[2]);
+    assert_eq!(t2.p3, obj.vertices[3]);
+    assert_eq!(obj.triangle_count(), 2);
+}
+
+#[test]
+fn vertex_normal_records() {
+    let file = "vn 0 0 1
+vn 0.707 0 -0.707
+vn 1 2 3";
+    let obj = ObjFile::parse(file);
+    assert_eq!(obj.normals[0], vector(0.0, 0.0, 1.0));
+    assert_eq!(obj.normals[1], vector(0.707, 0.0, -0.707));
+    assert_eq!(obj.normals[2], vector(1.0, 2.0, 3.0));
+}
+
+#[test]
+fn faces_with_normals() {
+    let file = "v 0 1 0
+v -1 0 0
+v 1 0 0
+
+vn -1 0 0
+vn 1 0 0
+vn 0 1 0
+
+f 1//3 2//1 3//2
+f 1/0/3 2/102/1 3/14/2";
+    let obj = ObjFile::parse(file);
+    let g = &obj.groups[0].triangles;
+    assert_eq!(obj.ignored_lines, 0);
+    for (shape_type, t) in g {
+        assert_eq!(*shape_type, ShapeType::SmoothTriangle);
+        assert_eq!(t.p1, obj.vertices[0]);
+        assert_eq!(t.p2, obj.vertices[1]);
+        assert_eq!(t.p3, obj.vertices[2]);
+        assert_eq!(t.n1, obj.normals[2]);
+        assert_eq!(t.n2, obj.normals[0]);
+        assert_eq!(t.n3, obj.normals[1]);
+    }
+}
+
+#[test]
+fn converting_an_obj_file_to_a_group() {
+    let file = "v -1 1 0
+v -1 0 0
+v 1 0 0
+v 1 1 0
+
+g FirstGroup
+f 1 2 3
+g SecondGroup
+f 1 3 4";
+    let obj = ObjFile::parse(file);
+    let mut world = World::default();
+    let group = obj.to_group(&mut world);
+    // The default group is empty so it gets skipped.
+    assert_eq!(group.children.len(), 2);
+    world.add_group(group);
+    assert_eq!(world.shapes.len(), 2);
+    assert_eq!(world.triangles.len(), 2);
+    assert_eq!(world.shapes[1].triangle_index, 1);
+}
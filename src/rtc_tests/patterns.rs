@@ -0,0 +1,120 @@
+use std::f32::consts::FRAC_PI_2;
+
+use crate::shader_types::{perlin_noise, turbulence, BumpMapping, PatternType, ShapeType, World};
+use glam::{vec3, vec3a, vec4, Mat4, Vec3A, Vec4};
+
+fn pattern_colour_at(world: &World, pattern_index: i32, p: Vec4) -> Vec3A {
+    let mut shape = ShapeType::Sphere.create();
+    shape.material.pattern_index = pattern_index;
+    unsafe { world.view().pattern_colour(shape, p) }
+}
+
+#[test]
+fn nested_patterns_are_relative_to_their_parent() {
+    let mut world = World::default();
+    let mut checkers = PatternType::Checker.create();
+    checkers.a = vec3a(1.0, 0.0, 0.0);
+    checkers.b = vec3a(0.0, 0.0, 1.0);
+    checkers.set_transform(Mat4::from_scale(vec3(0.5, 0.5, 0.5)));
+    let checkers = world.add_pattern(checkers);
+    let mut stripes = PatternType::Stripes.create();
+    stripes.a_index = checkers;
+    stripes.b = vec3a(1.0, 1.0, 1.0);
+    stripes.set_transform(Mat4::from_scale(vec3(2.0, 2.0, 2.0)));
+    let stripes = world.add_pattern(stripes);
+
+    // The checkers are scaled by both transforms so they're back to one unit wide.
+    assert_eq!(pattern_colour_at(&world, stripes, vec4(0.5, 0.0, 0.0, 1.0)), vec3a(1.0, 0.0, 0.0));
+    assert_eq!(pattern_colour_at(&world, stripes, vec4(1.5, 0.0, 0.0, 1.0)), vec3a(0.0, 0.0, 1.0));
+    assert_eq!(pattern_colour_at(&world, stripes, vec4(2.5, 0.0, 0.0, 1.0)), vec3a(1.0, 1.0, 1.0));
+}
+
+#[test]
+fn blended_patterns_mix_both_everywhere() {
+    let mut world = World::default();
+    let mut stripes = PatternType::Stripes.create();
+    stripes.a = vec3a(1.0, 1.0, 1.0);
+    stripes.b = vec3a(0.0, 0.0, 0.0);
+    let across = world.add_pattern(stripes);
+    stripes.set_transform(Mat4::from_rotation_y(FRAC_PI_2));
+    let along = world.add_pattern(stripes);
+    let mut blend = PatternType::Blend.create();
+    (blend.a_index, blend.b_index, blend.weight) = (across, along, 0.25);
+    let blend = world.add_pattern(blend);
+
+    let colour = |x, z| pattern_colour_at(&world, blend, vec4(x, 0.0, z, 1.0)).x;
+    assert_eq!(colour(0.5, -0.5), 1.0);
+    assert_eq!(colour(0.5, 0.5), 0.75);
+    assert_eq!(colour(1.5, -0.5), 0.25);
+    assert_eq!(colour(1.5, 0.5), 0.0);
+}
+
+#[test]
+fn noise_is_zero_on_the_grid_and_smooth_between() {
+    for i in -3..3 {
+        let p = vec4(i as f32, (i * 7) as f32, (i * 3) as f32, 1.0);
+        assert_eq!(unsafe { perlin_noise(p) }, 0.0);
+    }
+    let mut varied = false;
+    for i in 0..200 {
+        let p = vec4(i as f32 * 0.37, i as f32 * 0.11, i as f32 * -0.23, 1.0);
+        let (here, near) = unsafe { (perlin_noise(p), perlin_noise(p + vec4(0.001, 0.0, 0.0, 0.0))) };
+        assert!((-1.0..=1.0).contains(&here));
+        assert!((here - near).abs() < 0.01);
+        varied |= here.abs() > 0.1;
+        assert!((0.0..=1.0).contains(&unsafe { turbulence(p, 4) }));
+    }
+    assert!(varied);
+}
+
+#[test]
+fn perturbed_patterns_still_only_use_their_colours() {
+    let mut world = World::default();
+    let mut stripes = PatternType::Stripes.create();
+    stripes.a = vec3a(1.0, 1.0, 1.0);
+    let straight = world.add_pattern(stripes);
+    stripes.perturb = 0.5;
+    let perturbed = world.add_pattern(stripes);
+
+    let mut moved = false;
+    for i in 0..100 {
+        let p = vec4(i as f32 * 0.13, 0.3, i as f32 * 0.29, 1.0);
+        let colour = pattern_colour_at(&world, perturbed, p);
+        assert!(colour == stripes.a || colour == stripes.b);
+        moved |= colour != pattern_colour_at(&world, straight, p);
+    }
+    assert!(moved);
+}
+
+#[test]
+fn height_maps_tilt_the_normal_downhill() {
+    let mut world = World::default();
+    let mut gradient = PatternType::Gradient.create();
+    gradient.b = Vec3A::ONE;
+    let mut plane = ShapeType::Plane.create();
+    plane.material.bump_index = world.add_pattern(gradient);
+    plane.material.bump_amount = 0.5;
+
+    let normal = unsafe { world.view().bumped_normal(plane, vec4(0.5, 0.0, 0.0, 1.0), vec4(0.0, 1.0, 0.0, 0.0)) };
+    assert!(normal.abs_diff_eq(vec4(-0.5, 1.0, 0.0, 0.0).normalize(), 0.0001));
+}
+
+#[test]
+fn normal_maps_are_relative_to_the_surface() {
+    let mut world = World::default();
+    let mut sphere = ShapeType::Sphere.create();
+    sphere.material.bump = BumpMapping::NormalMap;
+    let mut solid = PatternType::Solid.create();
+    let point = vec4(0.0, 0.0, -1.0, 1.0);
+    let normal = vec4(0.0, 0.0, -1.0, 0.0);
+    let mut bumped = |colour| {
+        solid.a = colour;
+        sphere.material.bump_index = world.add_pattern(solid);
+        unsafe { world.view().bumped_normal(sphere, point, normal) }
+    };
+
+    // u goes right and v goes up when looking at the front.
+    assert!(bumped(vec3a(0.5, 0.5, 1.0)).abs_diff_eq(normal, 0.0001));
+    assert!(bumped(vec3a(1.0, 0.5, 0.5)).abs_diff_eq(vec4(1.0, 0.0, 0.0, 0.0), 0.0001));
+    assert!(bumped(vec3a(0.5, 1.0, 0.5)).abs_diff_eq(vec4(0.0, 1.0, 0.0, 0.0), 0.0001));
+}
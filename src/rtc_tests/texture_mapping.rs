@@ -0,0 +1,289 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::image::Image;
+use crate::rtc_tests::{assert_approx, point};
+use crate::shader_types::{
+    cube_uv, cylindrical_map, face_from_point, planar_map, spherical_map, texel_colour, float2, CubeFace, Pattern,
+    PatternType, ShapeType, Texture, UvMapping, World,
+};
+use glam::{vec3a, Vec3A, Vec4};
+
+const BLACK: Vec3A = Vec3A::ZERO;
+const WHITE: Vec3A = Vec3A::ONE;
+
+fn assert_uv(uv: float2, u: f32, v: f32) {
+    assert_approx(uv.x, u);
+    assert_approx(uv.y, v);
+}
+
+#[test]
+fn using_a_spherical_mapping_on_a_3d_point() {
+    let cases = [
+        (point(0.0, 0.0, -1.0), 0.0, 0.5),
+        (point(1.0, 0.0, 0.0), 0.25, 0.5),
+        (point(0.0, 0.0, 1.0), 0.5, 0.5),
+        (point(-1.0, 0.0, 0.0), 0.75, 0.5),
+        (point(0.0, 1.0, 0.0), 0.5, 1.0),
+        (point(0.0, -1.0, 0.0), 0.5, 0.0),
+        (point(FRAC_1_SQRT_2, FRAC_1_SQRT_2, 0.0), 0.25, 0.75),
+    ];
+    for (p, u, v) in cases {
+        assert_uv(unsafe { spherical_map(p) }, u, v);
+    }
+}
+
+/// A unit sphere (or cube) with a texture map pattern that looks up the uv patterns.
+fn mapped_colour(mapping: UvMapping, uv_patterns: &[Pattern], p: Vec4) -> Vec3A {
+    let mut world = World::default();
+    let mut map = PatternType::TextureMap.create();
+    map.mapping = mapping;
+    map.uv_pattern_index = world.patterns.len() as i32;
+    for &uv_pattern in uv_patterns {
+        world.add_pattern(uv_pattern);
+    }
+    let mut shape = ShapeType::Sphere.create();
+    shape.material.pattern_index = world.add_pattern(map);
+    unsafe { world.view().pattern_colour(shape, p) }
+}
+
+fn uv_colour(pattern: Pattern, u: f32, v: f32) -> Vec3A {
+    unsafe { World::default().view().uv_pattern_colour(pattern, float2 { x: u, y: v }) }
+}
+
+#[test]
+fn checker_pattern_in_2d() {
+    let checkers = Pattern::uv_checkers(2.0, 2.0, BLACK, WHITE);
+    let cases = [
+        (0.0, 0.0, BLACK),
+        (0.5, 0.0, WHITE),
+        (0.0, 0.5, WHITE),
+        (0.5, 0.5, BLACK),
+        (1.0, 1.0, BLACK),
+    ];
+    for (u, v, expected) in cases {
+        assert_eq!(uv_colour(checkers, u, v), expected);
+    }
+}
+
+#[test]
+fn using_a_texture_map_pattern_with_a_spherical_map() {
+    let checkers = Pattern::uv_checkers(16.0, 8.0, BLACK, WHITE);
+    let cases = [
+        (point(0.4315, 0.4670, 0.7719), WHITE),
+        (point(-0.9654, 0.2552, -0.0534), BLACK),
+        (point(0.1039, 0.7090, 0.6975), WHITE),
+        (point(-0.4986, -0.7856, -0.3663), BLACK),
+        (point(-0.0317, -0.9395, 0.3411), BLACK),
+        (point(0.4809, -0.7721, 0.4154), BLACK),
+        (point(0.0285, -0.9612, -0.2745), BLACK),
+        (point(-0.5734, -0.2162, -0.7903), WHITE),
+        (point(0.7688, -0.1470, 0.6223), BLACK),
+        (point(-0.7652, 0.2175, 0.6060), BLACK),
+    ];
+    for (p, expected) in cases {
+        assert_eq!(mapped_colour(UvMapping::SphericalMap, &[checkers], p), expected);
+    }
+}
+
+#[test]
+fn using_a_planar_mapping_on_a_3d_point() {
+    let cases = [
+        (point(0.25, 0.0, 0.5), 0.25, 0.5),
+        (point(0.25, 0.0, -0.25), 0.25, 0.75),
+        (point(0.25, 0.5, -0.25), 0.25, 0.75),
+        (point(1.25, 0.0, 0.5), 0.25, 0.5),
+        (point(0.25, 0.0, -1.75), 0.25, 0.25),
+        (point(1.0, 0.0, -1.0), 0.0, 0.0),
+        (point(0.0, 0.0, 0.0), 0.0, 0.0),
+    ];
+    for (p, u, v) in cases {
+        assert_uv(unsafe { planar_map(p) }, u, v);
+    }
+}
+
+#[test]
+fn using_a_cylindrical_mapping_on_a_3d_point() {
+    let cases = [
+        (point(0.0, 0.0, -1.0), 0.0, 0.0),
+        (point(0.0, 0.5, -1.0), 0.0, 0.5),
+        (point(0.0, 1.0, -1.0), 0.0, 0.0),
+        (point(FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), 0.125, 0.5),
+        (point(1.0, 0.5, 0.0), 0.25, 0.5),
+        (point(FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), 0.375, 0.5),
+        (point(0.0, -0.25, 1.0), 0.5, 0.75),
+        (point(-FRAC_1_SQRT_2, 0.5, FRAC_1_SQRT_2), 0.625, 0.5),
+        (point(-1.0, 1.25, 0.0), 0.75, 0.25),
+        (point(-FRAC_1_SQRT_2, 0.5, -FRAC_1_SQRT_2), 0.875, 0.5),
+    ];
+    for (p, u, v) in cases {
+        assert_uv(unsafe { cylindrical_map(p) }, u, v);
+    }
+}
+
+#[test]
+fn identifying_the_face_of_a_cube_from_a_point() {
+    let cases = [
+        (point(-1.0, 0.5, -0.25), CubeFace::CubeLeft),
+        (point(1.1, -0.75, 0.8), CubeFace::CubeRight),
+        (point(0.1, 0.6, 0.9), CubeFace::CubeFront),
+        (point(-0.7, 0.0, -2.0), CubeFace::CubeBack),
+        (point(0.5, 1.0, 0.9), CubeFace::CubeUp),
+        (point(-0.2, -1.3, 1.1), CubeFace::CubeDown),
+    ];
+    for (p, face) in cases {
+        assert_eq!(unsafe { face_from_point(p) }, face);
+    }
+}
+
+#[test]
+fn uv_mapping_each_face_of_a_cube() {
+    let cases = [
+        (CubeFace::CubeFront, point(-0.5, 0.5, 1.0), 0.25, 0.75),
+        (CubeFace::CubeFront, point(0.5, -0.5, 1.0), 0.75, 0.25),
+        (CubeFace::CubeBack, point(0.5, 0.5, -1.0), 0.25, 0.75),
+        (CubeFace::CubeBack, point(-0.5, -0.5, -1.0), 0.75, 0.25),
+        (CubeFace::CubeLeft, point(-1.0, 0.5, -0.5), 0.25, 0.75),
+        (CubeFace::CubeLeft, point(-1.0, -0.5, 0.5), 0.75, 0.25),
+        (CubeFace::CubeRight, point(1.0, 0.5, 0.5), 0.25, 0.75),
+        (CubeFace::CubeRight, point(1.0, -0.5, -0.5), 0.75, 0.25),
+        (CubeFace::CubeUp, point(-0.5, 1.0, -0.5), 0.25, 0.75),
+        (CubeFace::CubeUp, point(0.5, 1.0, 0.5), 0.75, 0.25),
+        (CubeFace::CubeDown, point(-0.5, -1.0, 0.5), 0.25, 0.75),
+        (CubeFace::CubeDown, point(0.5, -1.0, -0.5), 0.75, 0.25),
+    ];
+    for (face, p, u, v) in cases {
+        assert_uv(unsafe { cube_uv(face, p) }, u, v);
+    }
+}
+
+#[test]
+fn layout_of_the_align_check_pattern() {
+    let (main, ul, ur, bl, br) = (WHITE, vec3a(1.0, 0.0, 0.0), vec3a(1.0, 1.0, 0.0), vec3a(0.0, 1.0, 0.0), vec3a(0.0, 1.0, 1.0));
+    let pattern = Pattern::align_check(main, [ul, ur, bl, br]);
+    let cases = [(0.5, 0.5, main), (0.1, 0.9, ul), (0.9, 0.9, ur), (0.1, 0.1, bl), (0.9, 0.1, br)];
+    for (u, v, expected) in cases {
+        assert_eq!(uv_colour(pattern, u, v), expected);
+    }
+}
+
+#[test]
+fn finding_the_colours_on_a_mapped_cube() {
+    let red = vec3a(1.0, 0.0, 0.0);
+    let yellow = vec3a(1.0, 1.0, 0.0);
+    let brown = vec3a(1.0, 0.5, 0.0);
+    let green = vec3a(0.0, 1.0, 0.0);
+    let cyan = vec3a(0.0, 1.0, 1.0);
+    let blue = vec3a(0.0, 0.0, 1.0);
+    let purple = vec3a(1.0, 0.0, 1.0);
+    let white = WHITE;
+    // In the order of CubeFace.
+    let faces = [
+        Pattern::align_check(yellow, [cyan, red, blue, brown]),
+        Pattern::align_check(cyan, [red, yellow, brown, green]),
+        Pattern::align_check(red, [yellow, purple, green, white]),
+        Pattern::align_check(green, [purple, cyan, white, blue]),
+        Pattern::align_check(brown, [cyan, purple, red, yellow]),
+        Pattern::align_check(purple, [brown, green, blue, white]),
+    ];
+    let cases = [
+        (point(-1.0, 0.0, 0.0), yellow),
+        (point(-1.0, 0.9, -0.9), cyan),
+        (point(-1.0, 0.9, 0.9), red),
+        (point(-1.0, -0.9, -0.9), blue),
+        (point(-1.0, -0.9, 0.9), brown),
+        (point(0.0, 0.0, 1.0), cyan),
+        (point(-0.9, 0.9, 1.0), red),
+        (point(0.9, 0.9, 1.0), yellow),
+        (point(-0.9, -0.9, 1.0), brown),
+        (point(0.9, -0.9, 1.0), green),
+        (point(1.0, 0.0, 0.0), red),
+        (point(1.0, 0.9, 0.9), yellow),
+        (point(1.0, 0.9, -0.9), purple),
+        (point(1.0, -0.9, 0.9), green),
+        (point(1.0, -0.9, -0.9), white),
+        (point(0.0, 0.0, -1.0), green),
+        (point(0.9, 0.9, -1.0), purple),
+        (point(-0.9, 0.9, -1.0), cyan),
+        (point(0.9, -0.9, -1.0), white),
+        (point(-0.9, -0.9, -1.0), blue),
+        (point(0.0, 1.0, 0.0), brown),
+        (point(-0.9, 1.0, -0.9), cyan),
+        (point(0.9, 1.0, -0.9), purple),
+        (point(-0.9, 1.0, 0.9), red),
+        (point(0.9, 1.0, 0.9), yellow),
+        (point(0.0, -1.0, 0.0), purple),
+        (point(-0.9, -1.0, 0.9), brown),
+        (point(0.9, -1.0, 0.9), green),
+        (point(-0.9, -1.0, -0.9), blue),
+        (point(0.9, -1.0, -0.9), white),
+    ];
+    for (p, expected) in cases {
+        assert_eq!(mapped_colour(UvMapping::CubeMap, &faces, p), expected, "{}", p);
+    }
+}
+
+#[test]
+fn reading_a_file_with_the_wrong_magic_number() {
+    assert!(Image::parse_ppm(b"P32\n1 1\n255\n0 0 0\n").is_none());
+}
+
+#[test]
+fn reading_a_ppm_with_no_pixels_or_too_many() {
+    assert!(Image::parse_ppm(b"P3\n0 0\n255\n").is_none());
+    assert!(Image::parse_ppm(b"P3\n100000 100000\n255\n0 0 0\n").is_none());
+}
+
+#[test]
+fn reading_a_ppm_returns_an_image_of_the_right_size() {
+    let ppm = format!("P3\n10 2\n255\n{}", "0 0 0\n".repeat(20));
+    let image = Image::parse_ppm(ppm.as_bytes()).unwrap();
+    assert_eq!((image.width, image.height), (10, 2));
+}
+
+#[test]
+fn ppm_parsing_ignores_comments_and_respects_the_scale() {
+    let ppm = b"P3
+# this is a comment
+2 1
+# this, too
+100
+100 100 100
+# a comment in the middle
+50 50 50
+";
+    let image = Image::parse_ppm(ppm).unwrap();
+    assert_eq!(image.texels, vec![0xffffff, 0x808080]);
+}
+
+#[test]
+fn ppm_parsing_allows_an_rgb_triple_to_span_lines() {
+    let image = Image::parse_ppm(b"P3\n1 1\n255\n51\n153\n\n204\n").unwrap();
+    assert_eq!(image.texels, vec![51 | 153 << 8 | 204 << 16]);
+}
+
+/// Texels are stored as bytes so 0.9 comes back as 230 / 255.
+#[test]
+fn checker_pattern_in_2d_from_an_image() {
+    let ppm = b"P3
+10 10
+10
+0 0 0  1 1 1  2 2 2  3 3 3  4 4 4  5 5 5  6 6 6  7 7 7  8 8 8  9 9 9
+1 1 1  2 2 2  3 3 3  4 4 4  5 5 5  6 6 6  7 7 7  8 8 8  9 9 9  0 0 0
+2 2 2  3 3 3  4 4 4  5 5 5  6 6 6  7 7 7  8 8 8  9 9 9  0 0 0  1 1 1
+3 3 3  4 4 4  5 5 5  6 6 6  7 7 7  8 8 8  9 9 9  0 0 0  1 1 1  2 2 2
+4 4 4  5 5 5  6 6 6  7 7 7  8 8 8  9 9 9  0 0 0  1 1 1  2 2 2  3 3 3
+5 5 5  6 6 6  7 7 7  8 8 8  9 9 9  0 0 0  1 1 1  2 2 2  3 3 3  4 4 4
+6 6 6  7 7 7  8 8 8  9 9 9  0 0 0  1 1 1  2 2 2  3 3 3  4 4 4  5 5 5
+7 7 7  8 8 8  9 9 9  0 0 0  1 1 1  2 2 2  3 3 3  4 4 4  5 5 5  6 6 6
+8 8 8  9 9 9  0 0 0  1 1 1  2 2 2  3 3 3  4 4 4  5 5 5  6 6 6  7 7 7
+9 9 9  0 0 0  1 1 1  2 2 2  3 3 3  4 4 4  5 5 5  6 6 6  7 7 7  8 8 8
+";
+    let mut world = World::default();
+    let texture = world.add_texture(&Image::parse_ppm(ppm).unwrap());
+    let texture: Texture = world.textures[texture as usize];
+    let cases = [(0.0, 0.0, 0.9), (0.3, 0.0, 0.2), (0.6, 0.3, 0.1), (1.0, 1.0, 0.9)];
+    for (u, v, expected) in cases {
+        let colour = unsafe { texel_colour(texture, world.texels.as_ptr(), float2 { x: u, y: v }) };
+        assert!((colour - Vec3A::splat(expected)).abs().max_element() < 1.0 / 255.0, "{}", colour);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::rtc_tests::{assert_approx, point, vector};
+use crate::shader_types::{Intersection, Intersections, Ray, Shape, ShapeType, TriangleData, World};
+
+fn triangle_world() -> World {
+    let mut world = World::default();
+    let mut shape = ShapeType::Triangle.create();
+    shape.triangle_index = world.add_triangle(TriangleData::flat(
+        point(0.0, 1.0, 0.0),
+        point(-1.0, 0.0, 0.0),
+        point(1.0, 0.0, 0.0),
+    ));
+    world.add_shape(shape);
+    world
+}
+
+fn smooth_triangle_world() -> World {
+    let mut world = World::default();
+    let mut shape = ShapeType::SmoothTriangle.create();
+    shape.triangle_index = world.add_triangle(TriangleData::smooth(
+        point(0.0, 1.0, 0.0),
+        point(-1.0, 0.0, 0.0),
+        point(1.0, 0.0, 0.0),
+        vector(0.0, 1.0, 0.0),
+        vector(-1.0, 0.0, 0.0),
+        vector(1.0, 0.0, 0.0),
+    ));
+    world.add_shape(shape);
+    world
+}
+
+fn intersect(world: &World, ray: Ray) -> Intersections {
+    let shape: &Shape = &world.shapes[0];
+    let mut hits = Intersections::default();
+    unsafe { shape.intersect(&ray, &mut hits, world.triangles.as_ptr()) };
+    hits
+}
+
+#[test]
+fn constructing_a_triangle() {
+    let t = TriangleData::flat(point(0.0, 1.0, 0.0), point(-1.0, 0.0, 0.0), point(1.0, 0.0, 0.0));
+    assert_eq!(t.e1, vector(-1.0, -1.0, 0.0));
+    assert_eq!(t.e2, vector(1.0, -1.0, 0.0));
+    assert_eq!(t.normal, vector(0.0, 0.0, -1.0));
+}
+
+#[test]
+fn finding_the_normal_on_a_triangle() {
+    let world = triangle_world();
+    let hit = Intersection::new(1.0, 0);
+    for p in [point(0.0, 0.5, 0.0), point(-0.5, 0.75, 0.0), point(0.5, 0.25, 0.0)] {
+        let n = unsafe { world.shapes[0].normal_at(p, &hit, world.triangles.as_ptr()) };
+        assert_eq!(n, world.triangles[0].normal);
+    }
+}
+
+#[test]
+fn intersecting_a_ray_parallel_to_the_triangle() {
+    let hits = intersect(&triangle_world(), Ray::new(point(0.0, -1.0, -2.0), vector(0.0, 1.0, 0.0)));
+    assert_eq!(hits.count, 0);
+}
+
+#[test]
+fn a_ray_misses_the_triangle_edges() {
+    for origin in [point(1.0, 1.0, -2.0), point(-1.0, 1.0, -2.0), point(0.0, -1.0, -2.0)] {
+        let hits = intersect(&triangle_world(), Ray::new(origin, vector(0.0, 0.0, 1.0)));
+        assert_eq!(hits.count, 0);
+    }
+}
+
+#[test]
+fn a_ray_strikes_a_triangle() {
+    let hits = intersect(&triangle_world(), Ray::new(point(0.0, 0.5, -2.0), vector(0.0, 0.0, 1.0)));
+    assert_eq!(hits.count, 1);
+    assert_approx(hits.hits[0].t, 2.0);
+}
+
+#[test]
+fn an_intersection_with_a_smooth_triangle_stores_u_and_v() {
+    let hits = intersect(&smooth_triangle_world(), Ray::new(point(-0.2, 0.3, -2.0), vector(0.0, 0.0, 1.0)));
+    assert_eq!(hits.count, 1);
+    assert_approx(hits.hits[0].u, 0.45);
+    assert_approx(hits.hits[0].v, 0.25);
+}
+
+#[test]
+fn a_smooth_triangle_uses_u_and_v_to_interpolate_the_normal() {
+    let world = smooth_triangle_world();
+    let hit = Intersection { t: 1.0, obj: 0, u: 0.45, v: 0.25 };
+    let n = unsafe { world.shapes[0].normal_at(point(0.0, 0.0, 0.0), &hit, world.triangles.as_ptr()) };
+    assert_approx(n.x, -0.5547);
+    assert_approx(n.y, 0.83205);
+    assert_approx(n.z, 0.0);
+}
+
+#[test]
+fn preparing_the_normal_on_a_smooth_triangle() {
+    let world = smooth_triangle_world();
+    let hit = Intersection { t: 1.0, obj: 0, u: 0.45, v: 0.25 };
+    let r = Ray::new(point(-0.2, 0.3, -2.0), vector(0.0, 0.0, 1.0));
+    let comps = unsafe { world.view().prepare_comps(&hit, &r, &Intersections::default()) };
+    assert_approx(comps.normalv.x, -0.5547);
+    assert_approx(comps.normalv.y, 0.83205);
+    assert_approx(comps.normalv.z, 0.0);
+}
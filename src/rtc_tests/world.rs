@@ -0,0 +1,177 @@
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2};
+
+use crate::rtc_tests::{default_test_world, point, vector};
+use crate::shader_types::{sample_offset, Camera, Integrator, Light, Random, Ray, SamplePattern, ShapeType, World, MAX_RAY_QUEUE};
+use glam::{vec3, vec3a, vec4, Mat4, Vec3A, Vec4};
+
+/// The book's shade_hit recurses into reflections and refractions. Here colour_at does that with a queue instead,
+/// so these trace the whole ray rather than starting from the hit.
+fn colour_at(world: &World, ray: Ray) -> Vec3A {
+    unsafe { world.view().colour_at(&ray, &mut Random { state: 0 }) }
+}
+
+/// EPSILON is bigger than the book's (see common.h) which moves over_point and under_point enough to change the 4th decimal place.
+fn assert_colour(a: Vec3A, b: Vec3A) {
+    assert!((a - b).abs().max_element() < 0.001, "{} != {}", a, b);
+}
+
+/// A semi-transparent floor over a red ball.
+fn world_with_glass_floor(reflective: f32) -> World {
+    let mut w = default_test_world();
+    let mut floor = ShapeType::Plane.create();
+    floor.set_transform(Mat4::from_translation(vec3(0.0, -1.0, 0.0)));
+    floor.material.reflective = reflective;
+    floor.material.transparency = 0.5;
+    floor.material.refractive_index = 1.5;
+    w.add_shape(floor);
+    let mut ball = ShapeType::Sphere.create();
+    ball.material.colour = vec3a(1.0, 0.0, 0.0);
+    ball.material.ambient = 0.5;
+    // In the book the floor's shadow leaves the ball with just ambient light.
+    // Shadows let light through transparent shapes here, so turn off the rest to get the same colour.
+    ball.material.diffuse = 0.0;
+    ball.material.specular = 0.0;
+    ball.set_transform(Mat4::from_translation(vec3(0.0, -3.5, -0.5)));
+    w.add_shape(ball);
+    w
+}
+
+#[test]
+fn shade_hit_with_a_transparent_material() {
+    let w = world_with_glass_floor(0.0);
+    let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+    assert_colour(colour_at(&w, r), vec3a(0.93642, 0.68642, 0.68642));
+}
+
+#[test]
+fn shade_hit_with_a_reflective_transparent_material() {
+    let w = world_with_glass_floor(0.5);
+    let r = Ray::new(point(0.0, 0.0, -3.0), vector(0.0, -FRAC_1_SQRT_2, FRAC_1_SQRT_2));
+    assert_colour(colour_at(&w, r), vec3a(0.93391, 0.69643, 0.69243));
+}
+
+fn glass_world(max_ray_queue: u32) -> World {
+    let mut world = World::default();
+    let mut sphere = ShapeType::Sphere.create();
+    sphere.material.reflective = 0.5;
+    sphere.material.transparency = 0.5;
+    sphere.material.refractive_index = 1.5;
+    world.add_shape(sphere);
+    world.settings.max_ray_queue = max_ray_queue;
+    world
+}
+
+#[test]
+fn a_full_ray_queue_counts_the_rays_it_drops() {
+    let ray = Ray {
+        origin: vec4(0.0, 0.0, -5.0, 1.0),
+        direction: vec4(0.0, 0.0, 1.0, 0.0),
+    };
+
+    let world = glass_world(1);
+    unsafe { world.view().colour_at(&ray, &mut Random { state: 0 }) };
+    assert!(world.take_dropped_rays() > 0);
+    assert_eq!(world.take_dropped_rays(), 0);
+
+    let world = glass_world(MAX_RAY_QUEUE);
+    unsafe { world.view().colour_at(&ray, &mut Random { state: 0 }) };
+    assert_eq!(world.take_dropped_rays(), 0);
+}
+
+#[test]
+fn one_sample_goes_through_the_middle_of_the_pixel() {
+    let mut world = World {
+        camera: Camera::new(11, 11, FRAC_PI_2),
+        ..Default::default()
+    };
+    let mut sphere = ShapeType::Sphere.create();
+    sphere.set_transform(Mat4::from_translation(vec3(0.0, 0.0, -5.0)));
+    world.add_shape(sphere);
+    world.add_light(Light::point(vec4(-10.0, 10.0, 10.0, 1.0), vec3a(1.0, 1.0, 1.0)));
+
+    for (x, y) in [(5.0, 5.0), (3.0, 7.0), (0.0, 0.0)] {
+        let view = world.view();
+        let expected = unsafe { view.colour_at(&world.camera.ray_for_pixel(x, y), &mut Random { state: 0 }) };
+        assert_eq!(unsafe { view.pixel_colour(x, y) }, expected);
+    }
+}
+
+#[test]
+fn samples_stay_inside_the_pixel() {
+    let patterns = [SamplePattern::SampleGrid, SamplePattern::SampleRotatedGrid, SamplePattern::SampleJittered];
+    for pattern in patterns {
+        for n in 1..=16 {
+            let mut rng = Random { state: n };
+            let mut offsets = vec![];
+            for i in 0..n {
+                let offset = unsafe { sample_offset(pattern, i, n, &mut rng) };
+                assert!((0.0..1.0).contains(&offset.x) && (0.0..1.0).contains(&offset.y), "{:?} {:?}", pattern, offset);
+                assert!(!offsets.contains(&(offset.x, offset.y)), "{:?} repeated {:?}", pattern, offset);
+                offsets.push((offset.x, offset.y));
+            }
+        }
+    }
+}
+
+/// A floor with the light above it. Also a ceiling if there's something to bounce off.
+fn room(ceiling: bool) -> World {
+    let mut world = World::default();
+    world.add_shape(ShapeType::Plane.create());
+    if ceiling {
+        let mut ceiling = ShapeType::Plane.create();
+        ceiling.set_transform(Mat4::from_translation(vec3(0.0, 2.0, 0.0)));
+        world.add_shape(ceiling);
+    }
+    world.add_light(Light::point(vec4(0.0, 1.0, 0.0, 1.0), vec3a(1.0, 1.0, 1.0)));
+    world
+}
+
+#[test]
+fn path_tracing_replaces_ambient_with_bounced_light() {
+    let ray = Ray {
+        origin: vec4(0.0, 1.0, -1.0, 1.0),
+        direction: vec4(0.0, -1.0, 1.0, 0.0).normalize(),
+    };
+    let average = |world: &World| {
+        let mut rng = Random { state: 1 };
+        (0..1000).map(|_| unsafe { world.view().path_colour(&ray, &mut rng) }).sum::<Vec3A>() / 1000.0
+    };
+
+    // Nothing to bounce off so it's the same as colour_at without the ambient.
+    let world = room(false);
+    let direct = unsafe { world.view().colour_at(&ray, &mut Random { state: 0 }) } - world.shapes[0].material.colour * 0.1;
+    assert!(average(&world).abs_diff_eq(direct, 0.0001));
+
+    let bounced = average(&room(true));
+    assert!(bounced.x > direct.x + 0.05, "{} {}", bounced, direct);
+}
+
+#[test]
+fn emissive_surfaces_glow_without_any_lights() {
+    let mut world = room(true);
+    world.lights.clear();
+    world.shapes[1].material.emissive = vec3a(1.0, 0.5, 0.0);
+    let (up, down) = (vec4(0.0, 1.0, 0.0, 0.0), vec4(0.0, -1.0, 0.0, 0.0));
+    let colour = |world: &World, direction: Vec4, path_tracing: bool| {
+        let ray = Ray { origin: vec4(0.0, 1.0, 0.0, 1.0), direction };
+        let mut rng = Random { state: 1 };
+        if path_tracing {
+            (0..1000).map(|_| unsafe { world.view().path_colour(&ray, &mut rng) }).sum::<Vec3A>() / 1000.0
+        } else {
+            unsafe { world.view().colour_at(&ray, &mut rng) }
+        }
+    };
+    assert_eq!(colour(&world, up, false), vec3a(1.0, 0.5, 0.0));
+    assert_eq!(colour(&world, down, false), Vec3A::ZERO);
+
+    // Path tracing finds the ceiling's light by bouncing off the floor.
+    let lit = colour(&world, down, true);
+    assert!(lit.x > 0.1 && lit.z == 0.0, "{}", lit);
+
+    // A light standing in for the ceiling would count it twice.
+    world.settings.integrator = Integrator::PathTracing;
+    let mut light = Light::area(vec4(-1.0, 2.0, -1.0, 1.0), vec4(2.0, 0.0, 0.0, 0.0), 1, vec4(0.0, 0.0, 2.0, 0.0), 1, vec3a(1.0, 0.5, 0.0));
+    light.from_shape = true;
+    world.add_light(light);
+    assert_eq!(colour(&world, down, true), lit);
+}
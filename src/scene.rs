@@ -1,10 +1,17 @@
 use std::collections::HashMap;
+use std::{fmt, fs, io};
 use glam::{Mat4, Vec3, vec3, Vec3A, vec3a, Vec4};
 use yaml_rust::{ScanError, Yaml, YamlLoader};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
 use yaml_rust::yaml::{Array, Hash};
-use crate::bindings::{Camera, PatternType, Shape, ShapeType};
+use crate::bindings::{BumpMapping, Camera, CsgOperation, Integrator, Material, Pattern, PatternType, SamplePattern, Shape, ShapeType, TriangleData, UvMapping, MAX_HITS, MAX_LIGHT_STEPS, MAX_RAY_QUEUE, MAX_SAMPLES, PATTERN_STACK_SIZE};
+use crate::bvh::{self, Bounds, BvhStats};
+use crate::group::{Csg, Group};
+use crate::image::Image;
+use crate::obj::ObjFile;
 use crate::scene::SceneParseErr::ScanFailed;
-use crate::shader_types::{PointLight, World};
+use crate::shader_types::{is_frac, Light, World};
 
 /// Switch between these at runtime with the number keys.
 pub const SCENE_FILES: &[&str] = &[
@@ -20,78 +27,272 @@ pub const SCENE_FILES: &[&str] = &[
 #[derive(Debug)]
 pub enum SceneParseErr {
     ScanFailed(ScanError),
-    InvalidCameraSize
+    InvalidCameraSize,
+    /// The document must be a list of `add` and `define` entries.
+    NotAList,
+    MissingKey { at: Location, key: String },
+    WrongType { at: Location, key: String, expected: &'static str },
+    /// A string value that should refer to something (a template, shape, transform, etc.) didn't match anything.
+    UnknownName { at: Location, key: String, name: String, expected: &'static str },
+    /// A file referenced by the scene (like an obj mesh) couldn't be read.
+    FileFailed { at: Location, file: String, error: io::Error },
+    /// So many shapes that the shader can't walk the BVH around them.
+    BvhTooDeep(BvhStats),
+}
+
+/// Where an entry is in the top-level array of the scene file.
+/// The helpers that find problems don't know this, so it gets filled in by load_scene.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub entry: usize,
+    pub line: usize,
+}
+
+enum Child {
+    Shape(Shape),
+    Group(Group),
 }
 
 #[derive(Default)]
 struct ParseContext {
     world: World,
-    templates:  HashMap<String, Yaml>
+    templates:  HashMap<String, Yaml>,
+    /// Names of the templates currently being added, to catch ones that (eventually) include themselves.
+    instancing: Vec<String>,
+    /// Texture index of each image file that's been loaded, so using one again doesn't load another copy.
+    texture_files: HashMap<String, u32>,
+    /// The entry being parsed, so warnings can say where they came from.
+    at: Location,
+    /// Problems that don't stop the scene loading, like misspelled keys that would otherwise be silently ignored.
+    warnings: Vec<String>,
+    /// Set while parsing a bump or normal map. Only the brightness or direction matters there so patterns can leave out `colors`.
+    in_bump_map: bool,
 }
 
 // TODO: this would definitely be cleaner with serde but I find the systematic tediousness of doing it manually kinda pleasing.
-// TODO: make this give meaningful errors instead of panicking so I can make a REPL.
 /// Loads a scene description in the format used on https://forum.raytracerchallenge.com/board/4/gallery?q=scene+description
 pub fn load_scene(definition: &str) -> Result<World, SceneParseErr> {
+    let (world, warnings) = load_scene_with_warnings(definition)?;
+    for warning in warnings {
+        println!("Warning: {}", warning);
+    }
+    Ok(world)
+}
+
+fn load_scene_with_warnings(definition: &str) -> Result<(World, Vec<String>), SceneParseErr> {
     let data = YamlLoader::load_from_str(definition)?;
+    let lines = entry_lines(definition)?;
     let mut ctx = ParseContext::default();
 
-    if let Yaml::Array(data) = &data[0] {
-        for entry in data {
-            if let Some(entry) = entry.as_hash() {
-                entry.if_str("add", |name| ctx.handle_add(name, entry));
-                entry.if_str("define", |name| ctx.handle_define(name, entry));
-            }
-        }
+    let data = match data.first() {
+        Some(Yaml::Array(data)) => data,
+        _ => return Err(SceneParseErr::NotAList),
+    };
+    for (i, entry) in data.iter().enumerate() {
+        let at = Location {
+            entry: i,
+            line: lines.get(i).copied().unwrap_or_default(),
+        };
+        ctx.at = at;
+        ctx.handle_entry(entry).map_err(|e| e.located(at))?;
     }
 
-    ctx.build()
+    let warnings = std::mem::take(&mut ctx.warnings);
+    Ok((ctx.build()?, warnings))
 }
 
 impl ParseContext {
-    fn handle_add(&mut self, obj_type: &str, entry: &Hash) {
+    fn handle_entry(&mut self, entry: &Yaml) -> Result<(), SceneParseErr> {
+        let entry = entry.as_hash().ok_or_else(|| SceneParseErr::wrong_type("entry", "a map"))?;
+        if let Some(name) = entry.opt_str("add")? {
+            self.handle_add(name, entry)
+        } else if let Some(name) = entry.opt_str("define")? {
+            self.handle_define(name, entry)
+        } else if entry.opt_any("settings").is_some() {
+            entry.if_map("settings", |settings| self.parse_settings(settings))
+        } else {
+            Err(SceneParseErr::missing("add"))
+        }
+    }
+
+    fn handle_add(&mut self, obj_type: &str, entry: &Hash) -> Result<(), SceneParseErr> {
         match obj_type {
             "camera" => self.add_camera(entry),
             "light" => self.add_light(entry),
-            "plane" => self.add_shape(entry, ShapeType::Plane),
-            "sphere" => self.add_shape(entry, ShapeType::Sphere),
-            "cube" => self.add_shape(entry, ShapeType::Cube),
-            &_ => {}
+            _ => {
+                let first_shape = self.world.shapes.len();
+                match self.parse_child(obj_type, entry)? {
+                    Child::Shape(shape) => self.world.add_shape(shape),
+                    Child::Group(group) => self.world.add_group(group),
+                }
+                self.add_shape_light(entry, first_shape)
+            }
+        }
+    }
+
+    /// Anything that can go in a group's `children` list.
+    fn parse_child(&mut self, obj_type: &str, entry: &Hash) -> Result<Child, SceneParseErr> {
+        let shape = match obj_type {
+            "plane" => ShapeType::Plane.create(),
+            "sphere" => ShapeType::Sphere.create(),
+            "cube" => ShapeType::Cube.create(),
+            "cylinder" => ShapeType::Cylinder.create(),
+            "cone" => ShapeType::Cone.create(),
+            "triangle" => self.parse_triangle(entry)?,
+            "group" => return Ok(Child::Group(self.parse_group(entry)?)),
+            "csg" => return Ok(Child::Group(self.parse_csg(entry)?)),
+            "obj" => return Ok(Child::Group(self.parse_obj(entry)?)),
+            name => return Ok(Child::Group(self.parse_instance(name, entry)?)),
+        };
+        Ok(Child::Shape(self.parse_shape(entry, shape)?))
+    }
+
+    fn parse_group(&mut self, entry: &Hash) -> Result<Group, SceneParseErr> {
+        let mut group = self.parse_group_properties(entry)?;
+        for child in entry.get_list("children")? {
+            let child = child.as_hash().ok_or_else(|| SceneParseErr::wrong_type("children", "a list of maps"))?;
+            self.add_child(&mut group, &child.get_str("add")?, child)?;
+        }
+        Ok(group)
+    }
+
+    /// Children that don't set a material use the group's.
+    fn add_child(&mut self, group: &mut Group, obj_type: &str, child: &Hash) -> Result<(), SceneParseErr> {
+        let inherit_material = child.opt_any("material").is_none();
+        match self.parse_child(obj_type, child)? {
+            Child::Shape(shape) if inherit_material => group.add_shape_inheriting_material(shape),
+            Child::Shape(shape) => group.add_shape(shape),
+            Child::Group(child) => group.add_group(child),
+        }
+        Ok(())
+    }
+
+    /// The csg's transform and material go on a group around it.
+    fn parse_csg(&mut self, entry: &Hash) -> Result<Group, SceneParseErr> {
+        let operation = match entry.get_str("operation")?.as_str() {
+            "union" => CsgOperation::CsgUnion,
+            "intersection" => CsgOperation::CsgIntersection,
+            "difference" => CsgOperation::CsgDifference,
+            name => return Err(SceneParseErr::unknown("operation", name, "union, intersection or difference")),
+        };
+        let left = self.parse_csg_side(entry, "left")?;
+        let right = self.parse_csg_side(entry, "right")?;
+
+        let mut group = self.parse_group_properties(entry)?;
+        group.add_csg(Csg::new(operation, left, right));
+        Ok(group)
+    }
+
+    /// Each side is one child. Other scene files use `type` instead of `add` here so either works.
+    fn parse_csg_side(&mut self, entry: &Hash, key: &str) -> Result<Group, SceneParseErr> {
+        let side = entry.get_any(key)?.as_hash().ok_or_else(|| SceneParseErr::wrong_type(key, "a map"))?;
+        let obj_type = match side.opt_str("add")? {
+            Some(obj_type) => obj_type,
+            None => side.opt_str("type")?.ok_or_else(|| SceneParseErr::missing("add"))?,
+        };
+        let mut group = Group::default();
+        self.add_child(&mut group, obj_type, side)?;
+        Ok(group)
+    }
+
+    /// `add: <name>` where the name was defined as a shape or group. The instance's transform and material wrap it in a new group.
+    fn parse_instance(&mut self, name: &str, entry: &Hash) -> Result<Group, SceneParseErr> {
+        let template = match self.templates.get(name) {
+            Some(Yaml::Hash(template)) => template.clone(),
+            Some(_) => return Err(SceneParseErr::wrong_type("add", "the name of a map template")),
+            None => return Err(SceneParseErr::unknown("add", name, "a supported object type or defined shape")),
+        };
+        if self.instancing.iter().any(|n| n == name) {
+            return Err(SceneParseErr::unknown("add", name, "a template that doesn't end up adding itself"));
         }
+
+        let mut group = self.parse_group_properties(entry)?;
+        let template_type = template.get_str("add")?;
+        self.instancing.push(name.to_string());
+        let result = self.add_child(&mut group, &template_type, &template);
+        self.instancing.pop();
+        result?;
+        Ok(group)
     }
 
-    fn handle_define(&mut self, name: &str, entry: &Hash) {
-        let mut result = entry.get_any("value").unwrap().clone();
+    /// Groups only use the transform and material. The material is passed down to any children without their own.
+    fn parse_group_properties(&mut self, entry: &Hash) -> Result<Group, SceneParseErr> {
+        let mut group = Group {
+            material: self.opt_material(entry)?,
+            ..Default::default()
+        };
+        self.if_transform(entry, |t| group.transform = t)?;
+        Ok(group)
+    }
 
-        if let Some(Yaml::String(extend)) = entry.get_any("extend") {
-            let current = &result.as_hash().expect("Only type=Hash can extend.");
-            result = Yaml::Hash(self.extend_hash_template(current, extend.as_str()))
+    /// Each key is optional. Anything not set keeps the default from Settings.
+    fn parse_settings(&mut self, entry: &Hash) -> Result<(), SceneParseErr> {
+        let settings = &mut self.world.settings;
+        if entry.opt_any("max-reflect-refract").is_some() {
+            settings.max_reflect_refract = get_limit(entry, "max-reflect-refract", u32::MAX, "a whole number above 0")?;
+        }
+        if entry.opt_any("max-ray-queue").is_some() {
+            settings.max_ray_queue = get_limit(entry, "max-ray-queue", MAX_RAY_QUEUE, "a whole number from 1 to MAX_RAY_QUEUE (see common.h)")?;
+        }
+        if entry.opt_any("max-hits").is_some() {
+            settings.max_hits = get_limit(entry, "max-hits", MAX_HITS, "a whole number from 1 to MAX_HITS (see common.h)")?;
+        }
+        if entry.opt_any("samples").is_some() {
+            settings.samples = get_limit(entry, "samples", MAX_SAMPLES, "a whole number from 1 to MAX_SAMPLES (see common.h)")?;
+        }
+        if let Some(name) = entry.opt_str("sample-pattern")? {
+            settings.sample_pattern = match name {
+                "grid" => SamplePattern::SampleGrid,
+                "rotated-grid" => SamplePattern::SampleRotatedGrid,
+                "jittered" => SamplePattern::SampleJittered,
+                name => return Err(SceneParseErr::unknown("sample-pattern", name, "grid, rotated-grid or jittered")),
+            };
+        }
+        if let Some(name) = entry.opt_str("integrator")? {
+            settings.integrator = match name {
+                "whitted" => Integrator::WhittedTracing,
+                "path-tracing" => Integrator::PathTracing,
+                name => return Err(SceneParseErr::unknown("integrator", name, "whitted or path-tracing")),
+            };
+        }
+        Ok(())
+    }
+
+    fn handle_define(&mut self, name: &str, entry: &Hash) -> Result<(), SceneParseErr> {
+        let mut result = entry.get_any("value")?.clone();
+
+        if let Some(extend) = entry.opt_str("extend")? {
+            let current = &result.as_hash().ok_or_else(|| SceneParseErr::wrong_type("value", "a map (only maps can extend)"))?;
+            result = Yaml::Hash(self.extend_hash_template(current, extend)?)
         }
 
         if let Yaml::Array(current) = &result {
-            result = Yaml::Array(self.include_array_template(current));
+            result = Yaml::Array(self.include_array_template(current)?);
         }
 
         self.templates.insert(name.to_string(), result);
+        Ok(())
     }
 
     /// Merges values from current and template into a new Hash. When keys collide, current overrides template.
-    fn extend_hash_template(&self, current: &Hash, template_key: &str) -> Hash {
-        let prev = self.templates.get(template_key).unwrap();
-        let mut prev = prev.as_hash().expect("Hash can only extend type=Hash").clone();
+    fn extend_hash_template(&self, current: &Hash, template_key: &str) -> Result<Hash, SceneParseErr> {
+        let prev = self.templates.get(template_key)
+            .ok_or_else(|| SceneParseErr::unknown("extend", template_key, "a defined template"))?;
+        let mut prev = prev.as_hash().ok_or_else(|| SceneParseErr::wrong_type("extend", "the name of a map template"))?.clone();
         for (key, value) in current {
             prev.insert(key.clone(), value.clone());
         }
-        prev
+        Ok(prev)
     }
 
     /// Checks the `current` for any string entries that are keys for templates. Returns a new Array with those templates evaluated.
-    fn include_array_template(&self, current: &Array) -> Array {
+    fn include_array_template(&self, current: &Array) -> Result<Array, SceneParseErr> {
         let mut expanded = vec![];
         for value in current {
             if let Yaml::String(key) = &value {  // if its a string it might be a template key
                 if let Some(prev) = self.templates.get(key.as_str()) {  // if it was a template key
-                    let prev = prev.as_vec().unwrap().clone();  // only arrays can be merged with arrays
+                    // only arrays can be merged with arrays
+                    let prev = prev.as_vec().ok_or_else(|| SceneParseErr::wrong_type(key, "a list template"))?.clone();
                     for value in prev {
                         expanded.push(value);
                     }
@@ -103,213 +304,1078 @@ impl ParseContext {
             }
         }
 
-        expanded
+        Ok(expanded)
     }
 
-    fn add_shape(&mut self, entry: &Hash, shape: ShapeType) {
-        let mut shape = shape.create();
-        if let Some(m) = entry.get_any("material") {
-            let m = match m {
-                Yaml::Hash(m) => m,
-                Yaml::String(name) => match self.templates.get(name.as_str()) {
-                    Some(Yaml::Hash(m)) => m,
-                    _ => panic!("Undefined material name: {:?}", m),
-                },
-                _ => panic!("Invalid material value: {:?}", m),
-            }.clone();
+    /// The path is relative to the working directory, not the scene file.
+    fn parse_obj(&mut self, entry: &Hash) -> Result<Group, SceneParseErr> {
+        let file = entry.get_str("file")?;
+        let data = fs::read_to_string(&file).map_err(|error| SceneParseErr::FileFailed { at: Location::default(), file: file.clone(), error })?;
+        let obj = ObjFile::parse(&data);
+        println!("Loaded {} triangles in {} groups from {}.", obj.triangle_count(), obj.groups.len(), file);
+        if obj.ignored_lines > 0 {
+            println!("Ignored {} unsupported lines in {}.", obj.ignored_lines, file);
+        }
 
-            self.parse_material(&m, &mut shape);
+        let mut group = self.parse_group_properties(entry)?;
+        group.add_group(obj.to_group(&mut self.world));
+        Ok(group)
+    }
+
+    /// Applies the material, transform, etc. that any type of shape can have.
+    fn parse_shape(&mut self, entry: &Hash, mut shape: Shape) -> Result<Shape, SceneParseErr> {
+        if let Some(material) = self.opt_material(entry)? {
+            shape.material = material;
+        }
+
+        entry.if_bool("shadow", |v| shape.casts_shadow = v)?;
+        entry.if_bool("receive-shadow", |v| shape.receives_shadow = v)?;
+
+        entry.if_f32("min", |v| shape.minimum = v)?;
+        entry.if_f32("max", |v| shape.maximum = v)?;
+        entry.if_bool("closed", |v| shape.closed = v)?;
+
+        self.if_transform(entry, |t| shape.set_transform(t))?;
+        Ok(shape)
+    }
+
+    /// `area-light: true` on something emissive also adds a light where it is, for the Whitted integrator which only gets light from lights.
+    /// It's a jittered 4x4 rectangle through the middle of the shapes' bounding box, across its two longest sides.
+    /// The shapes stop casting shadows so they don't block their own light. Only works on things added at the top level.
+    fn add_shape_light(&mut self, entry: &Hash, first_shape: usize) -> Result<(), SceneParseErr> {
+        let mut area_light = false;
+        entry.if_bool("area-light", |v| area_light = v)?;
+        if !area_light {
+            return Ok(());
         }
+        let shapes = &self.world.shapes[first_shape..];
+        let emissive = shapes.iter().fold(Vec3A::ZERO, |e, shape| e.max(shape.material.emissive));
+        if emissive == Vec3A::ZERO {
+            return Err(SceneParseErr::wrong_type("area-light", "only used on shapes with an emissive material"));
+        }
+        let bounds = shapes.iter().map(|shape| bvh::world_bounds(shape, &self.world.triangles)).reduce(Bounds::union);
+        let bounds = match bounds {
+            Some(bounds) if !bounds.is_unbounded() => bounds,
+            _ => return Err(SceneParseErr::wrong_type("area-light", "only used on shapes that aren't infinitely big")),
+        };
+
+        // The light is flat so it goes across the middle of the box's thinnest side.
+        let size = bounds.max - bounds.min;
+        let thin = (0..3).min_by(|&a, &b| size[a].total_cmp(&size[b])).unwrap();
+        let (u, v) = ((thin + 1) % 3, (thin + 2) % 3);
+        let mut corner = bounds.min;
+        corner[thin] += size[thin] / 2.0;
+        let side = |axis: usize| (Vec3::AXES[axis] * size[axis]).extend(0.0);
+        let mut light = Light::area(corner.extend(1.0), side(u), 4, side(v), 4, emissive);
+        light.jitter = true;
+        light.from_shape = true;
+        self.world.add_light(light);
 
-        if let Some(&Yaml::Boolean(shadow)) = entry.get_any("shadow") {
-            if shadow {
-                // TODO: let shapes opt out of casting shadows.
+        for shape in &mut self.world.shapes[first_shape..] {
+            shape.casts_shadow = false;
+        }
+        self.world.shapes_changed();
+        Ok(())
+    }
+
+    /// Normals are optional but if there's any, all three are required.
+    fn parse_triangle(&mut self, entry: &Hash) -> Result<Shape, SceneParseErr> {
+        let (p1, p2, p3) = (entry.get_point("p1")?, entry.get_point("p2")?, entry.get_point("p3")?);
+        let (triangle, mut shape) = if entry.opt_any("n1").is_some() {
+            let (n1, n2, n3) = (entry.get_vec3("n1")?, entry.get_vec3("n2")?, entry.get_vec3("n3")?);
+            let triangle = TriangleData::smooth(p1, p2, p3, n1.extend(0.0), n2.extend(0.0), n3.extend(0.0));
+            (triangle, ShapeType::SmoothTriangle.create())
+        } else {
+            (TriangleData::flat(p1, p2, p3), ShapeType::Triangle.create())
+        };
+        shape.triangle_index = self.world.add_triangle(triangle);
+        Ok(shape)
+    }
+
+    /// The `material` key can be a map or the name of a defined one.
+    fn opt_material(&mut self, entry: &Hash) -> Result<Option<Material>, SceneParseErr> {
+        let m = match entry.opt_any("material") {
+            None => return Ok(None),
+            Some(Yaml::Hash(m)) => m,
+            Some(Yaml::String(name)) => match self.templates.get(name.as_str()) {
+                Some(Yaml::Hash(m)) => m,
+                Some(_) => return Err(SceneParseErr::wrong_type("material", "the name of a map template")),
+                None => return Err(SceneParseErr::unknown("material", name, "a defined material")),
+            },
+            Some(_) => return Err(SceneParseErr::wrong_type("material", "a map or the name of a defined material")),
+        }.clone();
+
+        let mut material = Material::default();
+        self.parse_material(&m, &mut material)?;
+        Ok(Some(material))
+    }
+
+    /// Unknown keys are probably typos, so they get a warning instead of being ignored silently.
+    fn parse_material(&mut self, m_obj: &Hash, material: &mut Material) -> Result<(), SceneParseErr> {
+        m_obj.if_frac("diffuse", |v| material.diffuse = v)?;
+        m_obj.if_frac("ambient", |v| material.ambient = v)?;
+        m_obj.if_frac("specular", |v| material.specular = v)?;
+        m_obj.if_f32("shininess", |v| material.shininess = v)?;
+        m_obj.if_frac("reflective", |v| material.reflective = v)?;
+        m_obj.if_frac("transparency", |v| material.transparency = v)?;
+        if let Some(index) = m_obj.opt_any("refractive-index") {
+            material.refractive_index = to_refractive_index(index)?;
+        }
+        m_obj.if_colour("color", |v| material.colour = v)?;
+        // Brighter than 1 is fine. It just reflects more light than it gets.
+        if material.colour.min_element() < 0.0 {
+            return Err(SceneParseErr::wrong_type("color", "a colour that isn't negative"));
+        }
+        if let Some(emissive) = m_obj.opt_any("emissive") {
+            // Just a number is how strongly it glows its own colour.
+            material.emissive = match maybe_f32(emissive) {
+                Some(strength) => material.colour * strength,
+                None => to_colour("emissive", emissive)?,
+            };
+            if material.emissive.min_element() < 0.0 {
+                return Err(SceneParseErr::wrong_type("emissive", "a colour or number that isn't negative"));
             }
         }
+        m_obj.if_map("pattern", |p| self.parse_pattern(p, material))?;
+        self.parse_bumps(m_obj, material)?;
 
-        self.if_transform(entry, |t| shape.set_transform(t));
-        self.world.add_shape(shape);
+        for key in m_obj.keys() {
+            match key.as_str() {
+                Some(key) if MATERIAL_KEYS.contains(&key) => {}
+                _ => self.warn(format!("unknown material key '{}'", to_display(key))),
+            }
+        }
+        Ok(())
     }
 
-    fn parse_material(&mut self, m_obj: &Hash, shape: &mut Shape) {
-        m_obj.if_f32("diffuse", |v| shape.material.diffuse = v);
-        m_obj.if_f32("ambient", |v| shape.material.ambient = v);
-        m_obj.if_f32("specular", |v| shape.material.specular = v);
-        m_obj.if_f32("shininess", |v| shape.material.shininess = v);
-        m_obj.if_f32("reflective", |v| shape.material.reflective = v);
-        m_obj.if_colour("color", |v| shape.material.colour = v);
-        m_obj.if_map("pattern", |p| self.parse_pattern(p, shape));
+    fn warn(&mut self, message: String) {
+        self.warnings.push(format!("At {}: {}.", self.at, message));
     }
 
-    fn parse_pattern(&mut self, p_obj: &Hash, shape: &mut Shape) {
-        let data = p_obj.get_any("colors").unwrap().as_vec().unwrap();
-        let mut pattern = get_pattern_type(&p_obj.get_str("type")).create();
-        pattern.a = to_colour(data[0].as_vec().unwrap());
-        pattern.b = to_colour(data[1].as_vec().unwrap());
-        self.if_transform(p_obj, |t| pattern.set_transform(t));
-        shape.material.pattern_index = self.world.add_pattern(pattern);
+    fn parse_pattern(&mut self, p_obj: &Hash, material: &mut Material) -> Result<(), SceneParseErr> {
+        material.pattern_index = self.add_pattern(p_obj, 1)?;
+        Ok(())
     }
 
-    fn if_transform(&self, obj: &Hash, action: impl FnOnce(Mat4)) {
-        if let Some(Yaml::Array(t)) = obj.get_any("transform") {
-            action(self.parse_transform(&t));
+    /// A `bump-map` pattern's brightness is the height of the surface. A `normal-map` pattern's colour is the normal.
+    /// `bump-amount` scales either of them.
+    fn parse_bumps(&mut self, m_obj: &Hash, material: &mut Material) -> Result<(), SceneParseErr> {
+        let (key, bump) = match (m_obj.opt_any("bump-map"), m_obj.opt_any("normal-map")) {
+            (None, None) => return Ok(()),
+            (Some(_), None) => ("bump-map", BumpMapping::HeightMap),
+            (None, Some(_)) => ("normal-map", BumpMapping::NormalMap),
+            (Some(_), Some(_)) => return Err(SceneParseErr::wrong_type("normal-map", "not used with bump-map")),
+        };
+        self.in_bump_map = true;
+        let result = m_obj.if_map(key, |p| {
+            material.bump_index = self.add_pattern(p, 1)?;
+            Ok(())
+        });
+        self.in_bump_map = false;
+        result?;
+        material.bump = bump;
+        m_obj.if_f32("bump-amount", |v| material.bump_amount = v)?;
+        Ok(())
+    }
+
+    /// Either of the `colors` can be another pattern instead. Its transform is relative to this one.
+    /// `blend` mixes in `weight` of the second one everywhere and `average` is a blend with a weight of 0.5.
+    fn add_pattern(&mut self, p_obj: &Hash, depth: u32) -> Result<i32, SceneParseErr> {
+        if depth > PATTERN_STACK_SIZE {
+            return Err(SceneParseErr::wrong_type("colors", "patterns nested at most PATTERN_STACK_SIZE deep (see common.h)"));
         }
+        let mut pattern = match p_obj.get_str("type")?.as_str() {
+            "map" => self.parse_texture_map(p_obj)?,
+            name => {
+                let mut pattern = get_pattern_type(name)?.create();
+                if name == "blend" {
+                    p_obj.if_f32("weight", |v| pattern.weight = v)?;
+                    if !(0.0..=1.0).contains(&pattern.weight) {
+                        return Err(SceneParseErr::wrong_type("weight", "a number from 0 to 1"));
+                    }
+                }
+                if self.in_bump_map && p_obj.opt_any("colors").is_none() {
+                    pattern.b = Vec3A::ONE;
+                    pattern
+                } else {
+                    let [a, b] = match p_obj.get_list("colors")?.as_slice() {
+                        [a, b] => [a, b],
+                        _ => return Err(SceneParseErr::wrong_type("colors", "a list of two colours or patterns")),
+                    };
+                    (pattern.a, pattern.a_index) = self.parse_pattern_slot(a, depth)?;
+                    (pattern.b, pattern.b_index) = self.parse_pattern_slot(b, depth)?;
+                    pattern
+                }
+            }
+        };
+        parse_noise(p_obj, &mut pattern)?;
+        self.if_transform(p_obj, |t| pattern.set_transform(t))?;
+        Ok(self.world.add_pattern(pattern))
+    }
+
+    /// A colour or a nested pattern's index (-1 if it's a colour).
+    fn parse_pattern_slot(&mut self, yaml: &Yaml, depth: u32) -> Result<(Vec3A, i32), SceneParseErr> {
+        match yaml {
+            Yaml::Hash(nested) => Ok((Vec3A::ZERO, self.add_pattern(nested, depth + 1)?)),
+            colour => Ok((to_colour("colors", colour)?, -1)),
+        }
+    }
+
+    /// A spherical, planar or cylindrical `mapping` has one `uv_pattern`.
+    /// A cube mapping has one for each face instead: `left`, `front`, `right`, `back`, `up` and `down`.
+    fn parse_texture_map(&mut self, p_obj: &Hash) -> Result<Pattern, SceneParseErr> {
+        let (mapping, keys): (_, &[&str]) = match p_obj.get_str("mapping")?.as_str() {
+            "spherical" => (UvMapping::SphericalMap, &["uv_pattern"]),
+            "planar" => (UvMapping::PlanarMap, &["uv_pattern"]),
+            "cylindrical" => (UvMapping::CylindricalMap, &["uv_pattern"]),
+            "cube" => (UvMapping::CubeMap, &["left", "front", "right", "back", "up", "down"]),
+            name => return Err(SceneParseErr::unknown("mapping", name, "spherical, planar, cylindrical or cube")),
+        };
+        // The shader expects a cube's faces to be next to each other so parse them all before adding any.
+        let uv_patterns = keys.iter().map(|key| self.parse_uv_pattern(p_obj, key)).collect::<Result<Vec<_>, _>>()?;
+
+        let mut pattern = PatternType::TextureMap.create();
+        pattern.mapping = mapping;
+        pattern.uv_pattern_index = self.world.patterns.len() as i32;
+        for uv_pattern in uv_patterns {
+            self.world.add_pattern(uv_pattern);
+        }
+        Ok(pattern)
+    }
+
+    /// `checkers` needs a `width`, `height` and two `colors`.
+    /// `align_check` needs `colors` to be a map with `main`, `ul`, `ur`, `bl` and `br`.
+    fn parse_uv_pattern(&mut self, p_obj: &Hash, key: &str) -> Result<Pattern, SceneParseErr> {
+        let uv_obj = p_obj.get_any(key)?.as_hash().ok_or_else(|| SceneParseErr::wrong_type(key, "a map"))?;
+        match uv_obj.get_str("type")?.as_str() {
+            "image" => {
+                let mut pattern = PatternType::UvImage.create();
+                pattern.texture_index = self.load_texture(&uv_obj.get_str("file")?)?;
+                Ok(pattern)
+            }
+            "checkers" | "uv_checkers" => {
+                let (width, height) = (uv_obj.get_f32("width")?, uv_obj.get_f32("height")?);
+                if width <= 0.0 || height <= 0.0 {
+                    return Err(SceneParseErr::wrong_type("width", "a number above 0 (and height too)"));
+                }
+                let (a, b) = get_two_colours(uv_obj)?;
+                Ok(Pattern::uv_checkers(width, height, a, b))
+            }
+            "align_check" => {
+                let colours = uv_obj.get_any("colors")?.as_hash().ok_or_else(|| SceneParseErr::wrong_type("colors", "a map of main, ul, ur, bl and br"))?;
+                let corners = [colours.get_colour("ul")?, colours.get_colour("ur")?, colours.get_colour("bl")?, colours.get_colour("br")?];
+                Ok(Pattern::align_check(colours.get_colour("main")?, corners))
+            }
+            name => Err(SceneParseErr::unknown("type", name, "a uv pattern (image, checkers or align_check)")),
+        }
+    }
+
+    /// Like obj files, the path is relative to the working directory.
+    fn load_texture(&mut self, file: &str) -> Result<u32, SceneParseErr> {
+        if let Some(&index) = self.texture_files.get(file) {
+            return Ok(index);
+        }
+        let image = Image::load(file).map_err(|error| SceneParseErr::FileFailed { at: Location::default(), file: file.to_string(), error })?;
+        println!("Loaded a {}x{} image from {}.", image.width, image.height, file);
+        let index = self.world.add_texture(&image);
+        self.texture_files.insert(file.to_string(), index);
+        Ok(index)
+    }
+
+    fn if_transform(&self, obj: &Hash, action: impl FnOnce(Mat4)) -> Result<(), SceneParseErr> {
+        if let Some(t) = obj.opt_any("transform") {
+            let t = t.as_vec().ok_or_else(|| SceneParseErr::wrong_type("transform", "a list"))?;
+            action(self.parse_transform(t)?);
+        }
+        Ok(())
     }
 
     // unlike material templates, the transformation lists on shapes sometimes use a template but add extra in place,
     // so this always checks if there are any templates to expand.
-    fn parse_transform(&self, t: &Array) -> Mat4 {
-        let t = self.include_array_template(t);
+    fn parse_transform(&self, t: &Array) -> Result<Mat4, SceneParseErr> {
+        let t = self.include_array_template(t)?;
         let mut transform = Mat4::IDENTITY;
         for part in t {
-            transform = to_mat(&part) * transform;
+            transform = to_mat(&part)? * transform;
         }
-        transform
+        Ok(transform)
     }
 
-    fn add_light(&mut self, entry: &Hash) {
-        self.world.add_light(PointLight {
-            position: entry.get_point("at"),
-            intensity: entry.get_colour("intensity"),
-        })
+    /// A point light has `at`. An area light has `corner`, `uvec`, `vvec`, `usteps` and `vsteps` instead.
+    /// A `direction` with `at` makes a spot light (which also needs `inner-angle` and `outer-angle`) or without it, a directional light.
+    /// Any of them can have `attenuation: [constant, linear, quadratic]` and `shadows: false`.
+    fn add_light(&mut self, entry: &Hash) -> Result<(), SceneParseErr> {
+        let intensity = entry.get_colour("intensity")?;
+        if intensity.min_element() < 0.0 {
+            return Err(SceneParseErr::wrong_type("intensity", "a colour that isn't negative"));
+        }
+        let mut light = if entry.opt_any("direction").is_some() {
+            let direction = entry.get_vec3("direction")?.extend(0.0);
+            if direction == Vec4::ZERO {
+                return Err(SceneParseErr::wrong_type("direction", "a non-zero vector"));
+            }
+            if entry.opt_any("at").is_some() {
+                let (inner, outer) = (entry.get_f32("inner-angle")?, entry.get_f32("outer-angle")?);
+                if inner > outer {
+                    return Err(SceneParseErr::wrong_type("inner-angle", "an angle no bigger than outer-angle"));
+                }
+                Light::spot(entry.get_point("at")?, direction, inner, outer, intensity)
+            } else {
+                Light::directional(direction, intensity)
+            }
+        } else if entry.opt_any("corner").is_some() {
+            Light::area(
+                entry.get_point("corner")?,
+                entry.get_vec3("uvec")?.extend(0.0),
+                get_limit(entry, "usteps", MAX_LIGHT_STEPS, "a whole number from 1 to MAX_LIGHT_STEPS (see common.h)")?,
+                entry.get_vec3("vvec")?.extend(0.0),
+                get_limit(entry, "vsteps", MAX_LIGHT_STEPS, "a whole number from 1 to MAX_LIGHT_STEPS (see common.h)")?,
+                intensity,
+            )
+        } else {
+            Light::point(entry.get_point("at")?, intensity)
+        };
+        entry.if_bool("jitter", |jitter| light.jitter = jitter)?;
+        entry.if_bool("shadows", |shadows| light.shadows = shadows)?;
+        if entry.opt_any("attenuation").is_some() {
+            let [constant, linear, quadratic] = entry.get_vec3("attenuation")?.to_array();
+            if constant < 0.0 || linear < 0.0 || quadratic < 0.0 || constant + linear + quadratic == 0.0 {
+                return Err(SceneParseErr::wrong_type("attenuation", "constant, linear and quadratic factors (not negative or all 0)"));
+            }
+            light.attenuation_constant = constant;
+            light.attenuation_linear = linear;
+            light.attenuation_quadratic = quadratic;
+        }
+        self.world.add_light(light);
+        Ok(())
     }
 
-    fn add_camera(&mut self, entry: &Hash) {
-        self.world.camera = Camera::new(entry.get_usize("width"), entry.get_usize("height"), entry.get_f32("field-of-view"));
+    fn add_camera(&mut self, entry: &Hash) -> Result<(), SceneParseErr> {
+        self.world.camera = Camera::new(entry.get_usize("width")?, entry.get_usize("height")?, entry.get_f32("field-of-view")?);
         self.world.camera.set_transform(Mat4::look_at_rh(
-            entry.get_vec3("from"),
-            entry.get_vec3("to"),
-            entry.get_vec3("up"),
-        ))
+            entry.get_vec3("from")?,
+            entry.get_vec3("to")?,
+            entry.get_vec3("up")?,
+        ));
+        Ok(())
     }
 
     fn build(self) -> Result<World, SceneParseErr> {
         if self.world.camera.vsize <= 0.0 || self.world.camera.hsize <= 0.0 {
-            Err(SceneParseErr::InvalidCameraSize)
-        } else {
-            Ok(self.world)
+            return Err(SceneParseErr::InvalidCameraSize);
         }
+        self.world.build_bvh().map_err(SceneParseErr::BvhTooDeep)?;
+        Ok(self.world)
     }
 }
 
 
 trait AssertMap<'a> {
-    fn get_any(self, key: &str) -> Option<&'a Yaml>;
-    fn if_str(self, key: &str, action: impl FnOnce(&str));
-    fn if_map(self, key: &str, action: impl FnOnce(&Hash));
-    fn get_f32(self, key: &str) -> f32;
-    fn get_usize(self, key: &str) -> usize;
-    fn get_vec3(self, key: &str) -> Vec3;
-    fn get_point(self, key: &str) -> Vec4;
-    fn get_colour(self, key: &str) -> Vec3A;
-    fn get_str(self, key: &str) -> String;
-    fn if_f32(self, key: &str, action: impl FnMut(f32));
-    fn if_colour(self, key: &str, action: impl FnMut(Vec3A));
+    fn opt_any(self, key: &str) -> Option<&'a Yaml>;
+    fn get_any(self, key: &str) -> Result<&'a Yaml, SceneParseErr>;
+    fn opt_str(self, key: &str) -> Result<Option<&'a str>, SceneParseErr>;
+    fn if_map(self, key: &str, action: impl FnOnce(&Hash) -> Result<(), SceneParseErr>) -> Result<(), SceneParseErr>;
+    fn if_bool(self, key: &str, action: impl FnOnce(bool)) -> Result<(), SceneParseErr>;
+    fn get_f32(self, key: &str) -> Result<f32, SceneParseErr>;
+    fn get_usize(self, key: &str) -> Result<usize, SceneParseErr>;
+    fn get_list(self, key: &str) -> Result<&'a Array, SceneParseErr>;
+    fn get_vec3(self, key: &str) -> Result<Vec3, SceneParseErr>;
+    fn get_point(self, key: &str) -> Result<Vec4, SceneParseErr>;
+    fn get_colour(self, key: &str) -> Result<Vec3A, SceneParseErr>;
+    fn get_str(self, key: &str) -> Result<String, SceneParseErr>;
+    fn if_f32(self, key: &str, action: impl FnOnce(f32)) -> Result<(), SceneParseErr>;
+    fn if_frac(self, key: &str, action: impl FnOnce(f32)) -> Result<(), SceneParseErr>;
+    fn if_colour(self, key: &str, action: impl FnOnce(Vec3A)) -> Result<(), SceneParseErr>;
 }
 
 impl<'a> AssertMap<'a> for &'a Hash {
-    fn get_any(self, key: &str) -> Option<&'a Yaml> {
+    fn opt_any(self, key: &str) -> Option<&'a Yaml> {
         let key = Yaml::String(key.to_string());
         self.get(&key)
     }
 
-    fn if_str(self, key: &str, action: impl FnOnce(&str)) {
-        if let Some(value) = self.get_any(key) {
-            let value = value.as_str().unwrap();
-            action(value);
+    fn get_any(self, key: &str) -> Result<&'a Yaml, SceneParseErr> {
+        self.opt_any(key).ok_or_else(|| SceneParseErr::missing(key))
+    }
+
+    fn opt_str(self, key: &str) -> Result<Option<&'a str>, SceneParseErr> {
+        match self.opt_any(key) {
+            Some(value) => value.as_str().map(Some).ok_or_else(|| SceneParseErr::wrong_type(key, "a string")),
+            None => Ok(None),
+        }
+    }
+
+    fn if_map(self, key: &str, action: impl FnOnce(&Hash) -> Result<(), SceneParseErr>) -> Result<(), SceneParseErr> {
+        if let Some(value) = self.opt_any(key) {
+            let value = value.as_hash().ok_or_else(|| SceneParseErr::wrong_type(key, "a map"))?;
+            action(value)?;
+        }
+        Ok(())
+    }
+
+    fn if_bool(self, key: &str, action: impl FnOnce(bool)) -> Result<(), SceneParseErr> {
+        if let Some(value) = self.opt_any(key) {
+            action(value.as_bool().ok_or_else(|| SceneParseErr::wrong_type(key, "true or false"))?);
         }
+        Ok(())
+    }
+
+    fn get_f32(self, key: &str) -> Result<f32, SceneParseErr> {
+        to_f32(key, self.get_any(key)?)
     }
 
-    fn if_map(self, key: &str, action: impl FnOnce(&Hash)) {
-        if let Some(value) = self.get_any(key) {
-            let value = value.as_hash().unwrap();
-            action(value);
+    fn get_usize(self, key: &str) -> Result<usize, SceneParseErr> {
+        match self.get_any(key)?.as_i64() {
+            Some(v) if v >= 0 => Ok(v as usize),
+            _ => Err(SceneParseErr::wrong_type(key, "a positive integer")),
         }
     }
 
-    fn get_f32(self, key: &str) -> f32 {
-        to_f32(self.get_any(key).unwrap())
+    fn get_list(self, key: &str) -> Result<&'a Array, SceneParseErr> {
+        self.get_any(key)?.as_vec().ok_or_else(|| SceneParseErr::wrong_type(key, "a list"))
     }
 
-    fn get_usize(self, key: &str) -> usize {
-        self.get_any(key).unwrap().as_i64().unwrap() as usize
+    fn get_vec3(self, key: &str) -> Result<Vec3, SceneParseErr> {
+        Ok(to_colour(key, self.get_any(key)?)?.into())
     }
 
-    fn get_vec3(self, key: &str) -> Vec3 {
-        let data = self.get_any(key).unwrap().as_vec().unwrap();
-        vec3(to_f32(&data[0]), to_f32(&data[1]), to_f32(&data[2]))
+    fn get_point(self, key: &str) -> Result<Vec4, SceneParseErr> {
+        Ok(self.get_vec3(key)?.extend(1.0))
     }
 
-    fn get_point(self, key: &str) -> Vec4 {
-        self.get_vec3(key).extend(1.0)
+    fn get_colour(self, key: &str) -> Result<Vec3A, SceneParseErr> {
+        to_colour(key, self.get_any(key)?)
     }
 
-    fn get_colour(self, key: &str) -> Vec3A {
-        self.get_vec3(key).into()
+    fn get_str(self, key: &str) -> Result<String, SceneParseErr> {
+        match self.get_any(key)?.as_str() {
+            Some(s) => Ok(s.to_string()),
+            None => Err(SceneParseErr::wrong_type(key, "a string")),
+        }
     }
 
-    fn get_str(self, key: &str) -> String {
-        self.get_any(key).unwrap().as_str().unwrap().to_string()
+    fn if_f32(self, key: &str, action: impl FnOnce(f32)) -> Result<(), SceneParseErr> {
+        if let Some(data) = self.opt_any(key) {
+            action(to_f32(key, data)?);
+        }
+        Ok(())
     }
 
-    fn if_f32(self, key: &str, mut action: impl FnMut(f32)) {
-        if let Some(data) = self.get_any(key) {
-            if let Some(v) = maybe_f32(data) {
-                action(v);
+    fn if_frac(self, key: &str, action: impl FnOnce(f32)) -> Result<(), SceneParseErr> {
+        if let Some(data) = self.opt_any(key) {
+            match to_f32(key, data)? {
+                v if is_frac(v) => action(v),
+                _ => return Err(SceneParseErr::wrong_type(key, "a number from 0 to 1")),
             }
         }
+        Ok(())
     }
 
-    fn if_colour(self, key: &str, mut action: impl FnMut(Vec3A)) {
-        if let Some(Yaml::Array(data)) = self.get_any(key) {
-            action(vec3a(to_f32(&data[0]), to_f32(&data[1]), to_f32(&data[2])))
+    fn if_colour(self, key: &str, action: impl FnOnce(Vec3A)) -> Result<(), SceneParseErr> {
+        if let Some(data) = self.opt_any(key) {
+            action(to_colour(key, data)?);
         }
+        Ok(())
+    }
+}
+
+/// Any list of three numbers. Used for points and vectors too, not just colours.
+fn to_colour(key: &str, yaml: &Yaml) -> Result<Vec3A, SceneParseErr> {
+    match yaml.as_vec().map(|data| data.as_slice()) {
+        Some([x, y, z]) => Ok(vec3a(to_f32(key, x)?, to_f32(key, y)?, to_f32(key, z)?)),
+        _ => Err(SceneParseErr::wrong_type(key, "a list of three numbers")),
     }
 }
 
-fn to_colour(data: &Array) -> Vec3A {
-    vec3a(to_f32(&data[0]), to_f32(&data[1]), to_f32(&data[2]))
+fn get_two_colours(entry: &Hash) -> Result<(Vec3A, Vec3A), SceneParseErr> {
+    match entry.get_list("colors")?.as_slice() {
+        [a, b] => Ok((to_colour("colors", a)?, to_colour("colors", b)?)),
+        _ => Err(SceneParseErr::wrong_type("colors", "a list of two colours")),
+    }
 }
 
-fn to_f32(yaml: &Yaml) -> f32 {
-    maybe_f32(yaml).unwrap()
+fn get_limit(entry: &Hash, key: &str, max: u32, expected: &'static str) -> Result<u32, SceneParseErr> {
+    match entry.get_usize(key) {
+        Ok(v) if v >= 1 && v <= max as usize => Ok(v as u32),
+        _ => Err(SceneParseErr::wrong_type(key, expected)),
+    }
 }
 
-fn to_mat(yaml: &Yaml) -> Mat4 {
-    let data = yaml.as_vec().unwrap();
-    let kind = data[0].as_str().unwrap();
-    match kind {
-        "translate" => Mat4::from_translation(offset_vec3(data)),
-        "scale" => Mat4::from_scale(offset_vec3(data)),
-        "rotate-x" => Mat4::from_rotation_x(to_f32(&data[1])),
-        "rotate-y" => Mat4::from_rotation_y(to_f32(&data[1])),
-        "rotate-z" => Mat4::from_rotation_z(to_f32(&data[1])),
-        &_ => panic!("Invalid transform {:?}", yaml),
+/// Any pattern can have `perturb`, `noise-scale` and `octaves` (see noise.h).
+fn parse_noise(p_obj: &Hash, pattern: &mut Pattern) -> Result<(), SceneParseErr> {
+    p_obj.if_f32("perturb", |v| pattern.perturb = v)?;
+    if pattern.perturb < 0.0 {
+        return Err(SceneParseErr::wrong_type("perturb", "a number that isn't negative"));
+    }
+    p_obj.if_f32("noise-scale", |v| pattern.noise_scale = v)?;
+    if pattern.noise_scale <= 0.0 {
+        return Err(SceneParseErr::wrong_type("noise-scale", "a number above 0"));
     }
+    if p_obj.opt_any("octaves").is_some() {
+        pattern.octaves = get_limit(p_obj, "octaves", 16, "a whole number from 1 to 16")?;
+    }
+    Ok(())
 }
 
-fn offset_vec3(data: &Array) -> Vec3 {
-    vec3(to_f32(&data[1]), to_f32(&data[2]), to_f32(&data[3]))
+const MATERIAL_KEYS: &[&str] = &[
+    "color", "pattern", "ambient", "diffuse", "specular", "shininess", "reflective", "transparency", "refractive-index",
+    "bump-map", "normal-map", "bump-amount", "emissive",
+];
+
+/// A number or the name of a common material. Values from the table in chapter 11 of the book.
+fn to_refractive_index(yaml: &Yaml) -> Result<f32, SceneParseErr> {
+    const EXPECTED: &str = "a number above 0 or vacuum, air, water, glass or diamond";
+    match yaml.as_str() {
+        Some("vacuum") => Ok(1.0),
+        Some("air") => Ok(1.00029),
+        Some("water") => Ok(1.333),
+        Some("glass") => Ok(1.52),
+        Some("diamond") => Ok(2.417),
+        Some(name) => Err(SceneParseErr::unknown("refractive-index", name, EXPECTED)),
+        None => maybe_f32(yaml).filter(|&index| index > 0.0).ok_or_else(|| SceneParseErr::wrong_type("refractive-index", EXPECTED)),
+    }
+}
+
+/// For mentioning a yaml value in a message.
+fn to_display(yaml: &Yaml) -> String {
+    match yaml {
+        Yaml::String(s) => s.clone(),
+        Yaml::Integer(v) => v.to_string(),
+        Yaml::Real(v) => v.clone(),
+        Yaml::Boolean(v) => v.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn to_f32(key: &str, yaml: &Yaml) -> Result<f32, SceneParseErr> {
+    maybe_f32(yaml).ok_or_else(|| SceneParseErr::wrong_type(key, "a number"))
+}
+
+fn to_mat(yaml: &Yaml) -> Result<Mat4, SceneParseErr> {
+    let data = yaml.as_vec().ok_or_else(|| SceneParseErr::wrong_type("transform", "a list of lists like [ scale, 1, 2, 3 ]"))?;
+    let kind = data.first().and_then(|k| k.as_str()).ok_or_else(|| SceneParseErr::wrong_type("transform", "a list starting with the name of a transformation"))?;
+    let mat = match kind {
+        "translate" => Mat4::from_translation(offset_vec3(kind, data)?),
+        "scale" => Mat4::from_scale(offset_vec3(kind, data)?),
+        "rotate-x" => Mat4::from_rotation_x(offset_f32(kind, data)?),
+        "rotate-y" => Mat4::from_rotation_y(offset_f32(kind, data)?),
+        "rotate-z" => Mat4::from_rotation_z(offset_f32(kind, data)?),
+        &_ => return Err(SceneParseErr::unknown("transform", kind, "a transformation (translate, scale, rotate-x, rotate-y, rotate-z)")),
+    };
+    Ok(mat)
+}
+
+fn offset_vec3(kind: &str, data: &Array) -> Result<Vec3, SceneParseErr> {
+    match data.as_slice() {
+        [_, x, y, z] => Ok(vec3(to_f32(kind, x)?, to_f32(kind, y)?, to_f32(kind, z)?)),
+        _ => Err(SceneParseErr::wrong_type(kind, "followed by three numbers")),
+    }
+}
+
+fn offset_f32(kind: &str, data: &Array) -> Result<f32, SceneParseErr> {
+    match data.as_slice() {
+        [_, v] => to_f32(kind, v),
+        _ => Err(SceneParseErr::wrong_type(kind, "followed by one number")),
+    }
 }
 
 fn maybe_f32(yaml: &Yaml) -> Option<f32> {
     match yaml {
-        Yaml::Real(_) => Some(yaml.as_f64().unwrap() as f32),
+        Yaml::Real(_) => Some(yaml.as_f64()? as f32),
         Yaml::Integer(v) => Some(*v as f32),
         _ => None
     }
 }
 
-fn get_pattern_type(name: &str) -> PatternType {
+fn get_pattern_type(name: &str) -> Result<PatternType, SceneParseErr> {
     match name {
-        "stripes" => PatternType::Stripes,
-        "checkers" => PatternType::Checker,
-        &_ => panic!("Invalid pattern type: {}", name),
+        "stripes" => Ok(PatternType::Stripes),
+        "gradient" => Ok(PatternType::Gradient),
+        "rings" => Ok(PatternType::Ring),
+        "checkers" => Ok(PatternType::Checker),
+        "blend" | "average" => Ok(PatternType::Blend),
+        "marble" => Ok(PatternType::Marble),
+        "wood" => Ok(PatternType::Wood),
+        "clouds" => Ok(PatternType::Clouds),
+        "ripples" => Ok(PatternType::Ripples),
+        &_ => Err(SceneParseErr::unknown("type", name, "a pattern type (stripes, gradient, rings, checkers, blend, average, marble, wood, clouds, ripples or map)")),
+    }
+}
+
+/// The Yaml tree doesn't remember where anything came from so parse again just to find the line each entry starts on.
+fn entry_lines(definition: &str) -> Result<Vec<usize>, ScanError> {
+    let mut lines = EntryLines::default();
+    Parser::new(definition.chars()).load(&mut lines, false)?;
+    Ok(lines.lines)
+}
+
+#[derive(Default)]
+struct EntryLines {
+    depth: usize,
+    lines: Vec<usize>,
+}
+
+impl MarkedEventReceiver for EntryLines {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        // The document is depth 0 and the top-level array is depth 1 so entries start at depth 1.
+        match ev {
+            Event::SequenceStart(_) | Event::MappingStart(_) => {
+                if self.depth == 1 {
+                    self.lines.push(mark.line());
+                }
+                self.depth += 1;
+            }
+            Event::SequenceEnd | Event::MappingEnd => self.depth -= 1,
+            Event::Scalar(..) | Event::Alias(_) if self.depth == 1 => self.lines.push(mark.line()),
+            _ => {}
+        }
     }
 }
 
+impl SceneParseErr {
+    fn missing(key: &str) -> Self {
+        SceneParseErr::MissingKey { at: Location::default(), key: key.to_string() }
+    }
+
+    fn wrong_type(key: &str, expected: &'static str) -> Self {
+        SceneParseErr::WrongType { at: Location::default(), key: key.to_string(), expected }
+    }
+
+    fn unknown(key: &str, name: &str, expected: &'static str) -> Self {
+        SceneParseErr::UnknownName { at: Location::default(), key: key.to_string(), name: name.to_string(), expected }
+    }
+
+    fn located(mut self, location: Location) -> Self {
+        match &mut self {
+            SceneParseErr::MissingKey { at, .. }
+            | SceneParseErr::WrongType { at, .. }
+            | SceneParseErr::UnknownName { at, .. }
+            | SceneParseErr::FileFailed { at, .. } => *at = location,
+            _ => {}
+        }
+        self
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {} (entry {} of the top-level list)", self.line, self.entry)
+    }
+}
+
+impl fmt::Display for SceneParseErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanFailed(e) => write!(f, "Invalid yaml: {}", e),
+            SceneParseErr::InvalidCameraSize => write!(f, "The scene needs a camera with a non-zero width and height."),
+            SceneParseErr::NotAList => write!(f, "The scene must be a list of add and define entries."),
+            SceneParseErr::MissingKey { at, key } => write!(f, "At {}: missing required key '{}'.", at, key),
+            SceneParseErr::WrongType { at, key, expected } => write!(f, "At {}: expected '{}' to be {}.", at, key, expected),
+            SceneParseErr::UnknownName { at, key, name, expected } => write!(f, "At {}: '{}: {}' is not {}.", at, key, name, expected),
+            SceneParseErr::FileFailed { at, file, error } => write!(f, "At {}: could not read '{}': {}.", at, file, error),
+            SceneParseErr::BvhTooDeep(stats) => write!(f, "Too many shapes for the shader's BVH stack. {}", stats),
+        }
+    }
+}
+
+impl std::error::Error for SceneParseErr {}
+
 impl From<ScanError> for SceneParseErr {
     fn from(value: ScanError) -> Self {
         ScanFailed(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_types::{LightType, Settings, UvCorner};
+    use glam::vec4;
+
+    /// Every scene needs a camera so this adds one before the entries being tested.
+    fn scene_with(entries: &str) -> String {
+        format!("
+- add: camera
+  width: 10
+  height: 10
+  field-of-view: 1
+  from: [ 0, 0, -5 ]
+  to: [ 0, 0, 0 ]
+  up: [ 0, 1, 0 ]
+{entries}")
+    }
+
+    #[test]
+    fn included_scenes_load() {
+        for scene in SCENE_FILES {
+            assert!(load_scene(scene).is_ok());
+        }
+    }
+
+    #[test]
+    fn errors_know_where_they_came_from() {
+        let scene = "
+- add: light
+  at: [ 0, 0, 0 ]
+  intensity: [ 1, 1, 1 ]
+
+- add: sphere
+  material:
+    diffuse: shiny
+";
+        match load_scene(scene) {
+            Err(SceneParseErr::WrongType { at, key, .. }) => {
+                assert_eq!(at, Location { entry: 1, line: 6 });
+                assert_eq!(key, "diffuse");
+            }
+            _ => panic!("Expected WrongType error."),
+        }
+    }
+
+    #[test]
+    fn groups_pass_down_transforms_and_materials() {
+        let scene = scene_with("
+- define: leg
+  value:
+    add: group
+    children:
+      - add: sphere
+        transform:
+          - [ translate, 1, 0, 0 ]
+      - add: cube
+        material:
+          color: [ 0, 0, 1 ]
+
+- add: leg
+  material:
+    color: [ 1, 0, 0 ]
+  transform:
+    - [ translate, 0, 2, 0 ]
+");
+        let world = load_scene(&scene).unwrap();
+        assert_eq!(world.shapes.len(), 2);
+        assert_eq!(world.shapes[0].material.colour, vec3a(1.0, 0.0, 0.0));
+        assert_eq!(world.shapes[1].material.colour, vec3a(0.0, 0.0, 1.0));
+        let centre = world.shapes[0].transform_inverse.inverse() * Vec4::new(0.0, 0.0, 0.0, 1.0);
+        assert_eq!(centre, Vec4::new(1.0, 2.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn csg_sides_are_flattened_before_the_csg() {
+        let scene = scene_with("
+- add: csg
+  operation: difference
+  left:
+    type: cube
+  right:
+    add: group
+    children:
+      - add: sphere
+      - add: cylinder
+");
+        let world = load_scene(&scene).unwrap();
+        assert_eq!(world.shapes.len(), 4);
+        assert_eq!(world.shapes[3].shape, ShapeType::Csg);
+        let node = world.csgs[0];
+        assert_eq!((node.first_left, node.first_right, node.end), (0, 1, 3));
+    }
+
+    #[test]
+    fn templates_cant_include_themselves() {
+        let scene = "
+- define: loop
+  value:
+    add: group
+    children:
+      - add: loop
+- add: loop
+";
+        assert!(matches!(load_scene(scene), Err(SceneParseErr::UnknownName { .. })));
+    }
+
+    #[test]
+    fn settings_override_the_defaults() {
+        let scene = scene_with("
+- settings:
+    max-reflect-refract: 3
+    max-ray-queue: 2
+    samples: 4
+    sample-pattern: rotated-grid
+    integrator: path-tracing
+");
+        let world = load_scene(&scene).unwrap();
+        assert_eq!(world.settings, Settings {
+            max_reflect_refract: 3,
+            max_ray_queue: 2,
+            samples: 4,
+            sample_pattern: SamplePattern::SampleRotatedGrid,
+            integrator: Integrator::PathTracing,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn settings_cant_go_past_the_shader_limits() {
+        let scene = "
+- settings:
+    max-hits: 100000
+";
+        assert!(matches!(load_scene(scene), Err(SceneParseErr::WrongType { .. })));
+        let scene = "
+- settings:
+    samples: 65
+";
+        assert!(matches!(load_scene(scene), Err(SceneParseErr::WrongType { .. })));
+    }
+
+    #[test]
+    fn lights_can_be_spot_or_directional_and_fade_with_distance() {
+        let scene = scene_with("
+- add: light
+  direction: [ 0, -2, 0 ]
+  intensity: [ 1, 1, 1 ]
+
+- add: light
+  at: [ 0, 5, 0 ]
+  direction: [ 0, -1, 0 ]
+  inner-angle: 0.3
+  outer-angle: 0.5
+  intensity: [ 1, 1, 1 ]
+  attenuation: [ 1, 0.5, 0.25 ]
+  shadows: false
+");
+        let world = load_scene(&scene).unwrap();
+        assert_eq!(world.lights[0].light_type, LightType::DirectionalLight);
+        assert_eq!(world.lights[0].direction, Vec4::new(0.0, -1.0, 0.0, 0.0));
+        assert_eq!(world.lights[1].light_type, LightType::SpotLight);
+        assert_eq!(world.lights[1].cos_outer, 0.5f32.cos());
+        assert_eq!(world.lights[1].attenuation_quadratic, 0.25);
+        assert!(world.lights[0].shadows && !world.lights[1].shadows);
+
+        let nowhere = scene.replace("[ 0, -2, 0 ]", "[ 0, 0, 0 ]");
+        assert!(matches!(load_scene(&nowhere), Err(SceneParseErr::WrongType { .. })));
+    }
+
+    #[test]
+    fn area_lights_have_at_most_max_light_steps() {
+        let scene = |steps: u32| scene_with(&format!("
+- add: light
+  corner: [ -1, 2, 4 ]
+  uvec: [ 2, 0, 0 ]
+  usteps: {steps}
+  vvec: [ 0, 2, 0 ]
+  vsteps: 2
+  intensity: [ 1, 1, 1 ]
+"));
+        assert_eq!(load_scene(&scene(MAX_LIGHT_STEPS)).unwrap().lights[0].samples(), MAX_LIGHT_STEPS * 2);
+        assert!(matches!(load_scene(&scene(MAX_LIGHT_STEPS + 1)), Err(SceneParseErr::WrongType { .. })));
+    }
+
+    #[test]
+    fn lights_can_be_bright_but_not_negative() {
+        let scene = |intensity: &str| scene_with(&format!("\n- add: light\n  at: [ 0, 5, 0 ]\n  intensity: {intensity}\n"));
+        assert_eq!(load_scene(&scene("[ 2, 2, 2 ]")).unwrap().lights[0].intensity, vec3a(2.0, 2.0, 2.0));
+        assert!(matches!(load_scene(&scene("[ 1, -1, 1 ]")), Err(SceneParseErr::WrongType { .. })));
+    }
+
+    #[test]
+    fn shapes_can_turn_off_shadows() {
+        let scene = scene_with("
+- add: sphere
+  shadow: false
+
+- add: plane
+  receive-shadow: false
+");
+        let world = load_scene(&scene).unwrap();
+        assert!(!world.shapes[0].casts_shadow && world.shapes[0].receives_shadow);
+        assert!(world.shapes[1].casts_shadow && !world.shapes[1].receives_shadow);
+    }
+
+    #[test]
+    fn materials_can_be_transparent() {
+        let scene = scene_with("
+- add: sphere
+  material:
+    transparency: 0.9
+    refractive-index: 1.5
+
+- add: sphere
+  material:
+    transparency: 1
+    refractive-index: diamond
+    shinyness: 300
+");
+        let (world, warnings) = load_scene_with_warnings(&scene).unwrap();
+        assert_eq!(world.shapes[0].material.transparency, 0.9);
+        assert_eq!(world.shapes[0].material.refractive_index, 1.5);
+        assert_eq!(world.shapes[1].material.refractive_index, 2.417);
+        assert_eq!(warnings, vec!["At line 15 (entry 2 of the top-level list): unknown material key 'shinyness'."]);
+
+        let zero = scene.replace("refractive-index: 1.5", "refractive-index: 0");
+        assert!(matches!(load_scene(&zero), Err(SceneParseErr::WrongType { .. })));
+    }
+
+    #[test]
+    fn materials_outside_their_range_are_errors() {
+        let scene = |material: &str| scene_with(&format!("\n- add: sphere\n  material: {{ {material} }}\n"));
+        let world = load_scene(&scene("color: [ 2, 1, 0.5 ]")).unwrap();
+        assert_eq!(world.shapes[0].material.colour, vec3a(2.0, 1.0, 0.5));
+
+        for bad in ["color: [ -1, 0, 0 ]", "diffuse: 1.5", "ambient: -0.1", "specular: 2", "reflective: 1.1", "transparency: -1"] {
+            assert!(matches!(load_scene(&scene(bad)), Err(SceneParseErr::WrongType { .. })), "{bad}");
+        }
+    }
+
+    #[test]
+    fn texture_maps_load_each_image_once() {
+        let file = std::env::temp_dir().join("texture_maps_load_each_image_once.ppm");
+        fs::write(&file, "P3\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        let scene = scene_with(&format!("
+- add: cube
+  material:
+    pattern:
+      type: map
+      mapping: cube
+      left: {{ type: image, file: {0} }}
+      front: {{ type: image, file: {0} }}
+      right: {{ type: image, file: {0} }}
+      back: {{ type: image, file: {0} }}
+      up: {{ type: image, file: {0} }}
+      down: {{ type: image, file: {0} }}
+", file.display()));
+        let result = load_scene(&scene);
+        fs::remove_file(&file).unwrap();
+        let world = result.unwrap();
+        assert_eq!(world.textures.len(), 1);
+        assert_eq!(world.texels, vec![0x0000ff, 0xff0000]);
+        let map = world.patterns[world.shapes[0].material.pattern_index as usize];
+        assert_eq!((map.pattern, map.mapping, map.uv_pattern_index), (PatternType::TextureMap, UvMapping::CubeMap, 0));
+    }
+
+    #[test]
+    fn uv_patterns_use_the_books_names() {
+        let scene = scene_with("
+- add: sphere
+  material:
+    pattern:
+      type: map
+      mapping: spherical
+      uv_pattern:
+        type: checkers
+        width: 16
+        height: 8
+        colors:
+          - [ 0, 0, 0 ]
+          - [ 1, 1, 1 ]
+
+- add: plane
+  material:
+    pattern:
+      type: map
+      mapping: planar
+      uv_pattern:
+        type: align_check
+        colors:
+          main: [ 1, 1, 1 ]
+          ul: [ 1, 0, 0 ]
+          ur: [ 1, 1, 0 ]
+          bl: [ 0, 1, 0 ]
+          br: [ 0, 1, 1 ]
+");
+        let world = load_scene(&scene).unwrap();
+        let checkers = world.patterns[0];
+        assert_eq!((checkers.pattern, checkers.width, checkers.height), (PatternType::UvCheckers, 16.0, 8.0));
+        let align_check = world.patterns[2];
+        assert_eq!(align_check.pattern, PatternType::UvAlignCheck);
+        assert_eq!(align_check.corners[UvCorner::BottomRight as usize], vec3a(0.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn patterns_can_be_nested_in_colors() {
+        let nested = |depth: usize| scene_with(&format!("
+- add: sphere
+  material:
+    pattern: {}[ 1, 1, 1 ]{}
+", "{ type: average, colors: [ [ 0, 0, 0 ], ".repeat(depth), " ] }".repeat(depth)));
+        let world = load_scene(&nested(3)).unwrap();
+        assert_eq!(world.patterns.len(), 3);
+        assert_eq!(world.shapes[0].material.pattern_index, 2);
+        assert_eq!((world.patterns[2].a_index, world.patterns[2].b_index), (-1, 1));
+        assert_eq!(world.patterns[0].b, vec3a(1.0, 1.0, 1.0));
+
+        assert!(load_scene(&nested(PATTERN_STACK_SIZE as usize)).is_ok());
+        assert!(matches!(load_scene(&nested(PATTERN_STACK_SIZE as usize + 1)), Err(SceneParseErr::WrongType { .. })));
+    }
+
+    #[test]
+    fn patterns_can_have_noise() {
+        let scene = |pattern: &str| scene_with(&format!("
+- add: sphere
+  material:
+    pattern: {{ colors: [ [ 1, 1, 1 ], [ 0, 0, 0 ] ], {pattern} }}
+"));
+        let world = load_scene(&scene("type: marble, noise-scale: 0.5, octaves: 6")).unwrap();
+        let marble = world.patterns[0];
+        assert_eq!(marble.pattern, PatternType::Marble);
+        assert_eq!((marble.perturb, marble.noise_scale, marble.octaves), (1.0, 0.5, 6));
+
+        let world = load_scene(&scene("type: stripes, perturb: 0.25")).unwrap();
+        assert_eq!((world.patterns[0].perturb, world.patterns[0].octaves), (0.25, 4));
+
+        for bad in ["type: clouds, octaves: 0", "type: wood, noise-scale: 0", "type: rings, perturb: -1"] {
+            assert!(matches!(load_scene(&scene(bad)), Err(SceneParseErr::WrongType { .. })));
+        }
+    }
+
+    #[test]
+    fn materials_can_have_bump_or_normal_maps() {
+        let scene = |material: &str| scene_with(&format!("
+- add: sphere
+  material: {{ color: [ 1, 0, 0 ], {material} }}
+"));
+        let world = load_scene(&scene("bump-map: { type: ripples }, bump-amount: 0.1")).unwrap();
+        let material = world.shapes[0].material;
+        assert_eq!((material.pattern_index, material.bump_index, material.bump, material.bump_amount), (-1, 0, BumpMapping::HeightMap, 0.1));
+        assert_eq!((world.patterns[0].a, world.patterns[0].b), (Vec3A::ZERO, Vec3A::ONE));
+
+        let world = load_scene(&scene("normal-map: { type: stripes, colors: [ [ 0.5, 0.5, 1 ], [ 1, 0.5, 0.5 ] ] }")).unwrap();
+        assert_eq!((world.shapes[0].material.bump, world.shapes[0].material.bump_amount), (BumpMapping::NormalMap, 1.0));
+
+        let both = scene("bump-map: { type: clouds }, normal-map: { type: clouds }");
+        assert!(matches!(load_scene(&both), Err(SceneParseErr::WrongType { .. })));
+
+        // Only bump and normal maps can leave out the colours.
+        let plain = scene("pattern: { type: stripes, colours: [ [ 1, 0, 0 ], [ 0, 0, 1 ] ] }");
+        assert!(matches!(load_scene(&plain), Err(SceneParseErr::MissingKey { .. })));
+    }
+
+    #[test]
+    fn emissive_shapes_can_be_area_lights() {
+        let scene = |shape: &str| scene_with(&format!("\n{shape}\n"));
+        let world = load_scene(&scene("- add: sphere\n  material: { color: [ 1, 0.5, 0 ], emissive: 0.5 }")).unwrap();
+        assert_eq!(world.shapes[0].material.emissive, vec3a(0.5, 0.25, 0.0));
+        assert!(world.lights.is_empty() && world.shapes[0].casts_shadow);
+
+        let panel = "
+- add: cube
+  material: { emissive: [ 1, 1, 0.8 ] }
+  area-light: true
+  transform:
+    - [ scale, 2, 0.1, 1 ]
+    - [ translate, 0, 3, 0 ]";
+        let world = load_scene(&scene(panel)).unwrap();
+        let light = world.lights[0];
+        assert!(light.from_shape && light.jitter && !world.shapes[0].casts_shadow);
+        assert_eq!((light.intensity, light.samples()), (vec3a(1.0, 1.0, 0.8), 16));
+        assert!(light.corner.abs_diff_eq(vec4(-2.0, 3.0, -1.0, 1.0), 0.0001), "{}", light.corner);
+        assert!(light.position.abs_diff_eq(vec4(0.0, 3.0, 0.0, 1.0), 0.0001), "{}", light.position);
+        let bright = load_scene(&scene(&panel.replace("[ 1, 1, 0.8 ]", "[ 4, 4, 3.2 ]"))).unwrap();
+        assert_eq!(bright.lights[0].intensity, vec3a(4.0, 4.0, 3.2));
+
+        let not_emissive = scene("- add: sphere\n  area-light: true");
+        assert!(matches!(load_scene(&not_emissive), Err(SceneParseErr::WrongType { .. })));
+        let infinite = scene("- add: plane\n  material: { emissive: 1 }\n  area-light: true");
+        assert!(matches!(load_scene(&infinite), Err(SceneParseErr::WrongType { .. })));
+    }
+}
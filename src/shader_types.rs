@@ -1,12 +1,57 @@
 pub use crate::bindings::*;
-use glam::{Mat4, Vec3A};
+use crate::bvh::{self, BvhStats};
+use crate::group::{Csg, Group};
+use crate::image::Image;
+use glam::{Mat4, Vec3A, Vec4};
+use std::cell::OnceCell;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 #[derive(Default)]
 pub struct World {
+    /// Call shapes_changed after editing these directly (instead of with add_shape) so the BVH gets rebuilt.
     pub shapes: Vec<Shape>,
-    pub lights: Vec<PointLight>,
+    pub lights: Vec<Light>,
     pub patterns: Vec<Pattern>,
+    pub textures: Vec<Texture>,
+    /// Every texture's pixels one after another (see Texture::offset).
+    pub texels: Vec<u32>,
+    pub triangles: Vec<TriangleData>,
+    pub csgs: Vec<CsgNode>,
     pub camera: Camera,
+    pub settings: Settings,
+    /// Built the first time it's needed after the shapes change.
+    pub(crate) bvh: OnceCell<Vec<BvhNode>>,
+    /// Written by the shader (every pixel at once) through WorldView::dropped_rays.
+    pub(crate) dropped_rays: AtomicU32,
+}
+
+/// Limits that can change per scene (the `settings` entry in a scene file) or while running.
+/// They're copied into ShaderInputs every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// How many rays are traced for one pixel (the first one plus reflections and refractions).
+    pub max_reflect_refract: u32,
+    /// How many reflections and refractions can be waiting to be traced. Any more get dropped. At most MAX_RAY_QUEUE.
+    pub max_ray_queue: u32,
+    /// How many intersections are kept for one ray. Any more get dropped, furthest first. At most MAX_HITS.
+    pub max_hits: u32,
+    /// Rays per pixel for anti-aliasing. Everything takes this many times longer. At most MAX_SAMPLES.
+    pub samples: u32,
+    pub sample_pattern: SamplePattern,
+    pub integrator: Integrator,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            max_reflect_refract: 10,
+            max_ray_queue: 5,
+            max_hits: MAX_HITS,
+            samples: 1,
+            sample_pattern: SamplePattern::SampleGrid,
+            integrator: Integrator::WhittedTracing,
+        }
+    }
 }
 
 impl World {
@@ -17,29 +62,75 @@ impl World {
                 && is_frac(shape.material.reflective)
                 && is_frac(shape.material.specular)
                 && is_frac(shape.material.transparency)
-                && is_colour(shape.material.colour)
+                && shape.material.colour.min_element() >= 0.0
                 && shape.material.refractive_index >= 0.0
         );
 
         shape.index = self.shapes.len() as u32;
         self.shapes.push(shape);
+        self.shapes_changed();
+    }
+
+    /// Flattens the tree (see group.rs) and adds every shape in it.
+    pub fn add_group(&mut self, group: Group) {
+        group.flatten(self, Mat4::IDENTITY, None, false);
+    }
+
+    pub fn add_csg(&mut self, csg: Csg) {
+        let mut group = Group::default();
+        group.add_csg(csg);
+        self.add_group(group);
+    }
+
+    /// The node data used by a flattened Csg (see group.rs).
+    pub(crate) fn add_csg_node(&mut self, csg: CsgNode) -> u32 {
+        self.csgs.push(csg);
+        self.shapes_changed();
+        (self.csgs.len() - 1) as u32
     }
 
-    pub fn add_light(&mut self, light: PointLight) {
-        debug_assert!(is_colour(light.intensity));
+    /// Throws away the BVH so it gets rebuilt around the new shapes.
+    pub fn shapes_changed(&mut self) {
+        self.bvh.take();
+    }
+
+    pub fn add_light(&mut self, light: Light) {
+        debug_assert!(light.intensity.min_element() >= 0.0 && light.samples() > 0);
+        debug_assert!(light.attenuation_constant >= 0.0 && light.attenuation_linear >= 0.0 && light.attenuation_quadratic >= 0.0);
         self.lights.push(light);
     }
 
+    /// Any patterns it uses in place of a or b need to be added first so they can't make a loop.
     pub fn add_pattern(&mut self, pattern: Pattern) -> i32 {
+        debug_assert!(pattern.a_index < self.patterns.len() as i32 && pattern.b_index < self.patterns.len() as i32);
         self.patterns.push(pattern);
         (self.patterns.len() - 1) as i32
     }
 
+    /// Set the returned index as the texture_index of a UvImage pattern.
+    pub fn add_texture(&mut self, image: &Image) -> u32 {
+        debug_assert!(image.width > 0 && image.height > 0 && image.texels.len() == (image.width * image.height) as usize);
+        self.textures.push(Texture {
+            offset: self.texels.len() as u32,
+            width: image.width,
+            height: image.height,
+        });
+        self.texels.extend_from_slice(&image.texels);
+        (self.textures.len() - 1) as u32
+    }
+
+    /// Set the returned index as the triangle_index of a Triangle or SmoothTriangle shape.
+    pub fn add_triangle(&mut self, triangle: TriangleData) -> u32 {
+        self.triangles.push(triangle);
+        self.shapes_changed();
+        (self.triangles.len() - 1) as u32
+    }
+
     pub fn get_shapes(&self) -> &[Shape] {
         self.shapes.as_slice()
     }
 
-    pub fn get_lights(&self) -> &[PointLight] {
+    pub fn get_lights(&self) -> &[Light] {
         self.lights.as_slice()
     }
 
@@ -47,31 +138,89 @@ impl World {
         self.patterns.as_slice()
     }
 
+    pub fn get_textures(&self) -> &[Texture] {
+        self.textures.as_slice()
+    }
+
+    pub fn get_texels(&self) -> &[u32] {
+        self.texels.as_slice()
+    }
+
+    pub fn get_triangles(&self) -> &[TriangleData] {
+        self.triangles.as_slice()
+    }
+
+    pub fn get_csgs(&self) -> &[CsgNode] {
+        self.csgs.as_slice()
+    }
+
+    /// Builds the BVH now instead of on first use, so a tree too deep for the shader is an error instead of a panic.
+    pub fn build_bvh(&self) -> Result<(), BvhStats> {
+        if self.bvh.get().is_none() {
+            let _ = self.bvh.set(bvh::build(&self.shapes, &self.triangles, &self.csgs)?);
+        }
+        Ok(())
+    }
+
+    pub fn get_bvh(&self) -> &[BvhNode] {
+        self.bvh.get_or_init(|| {
+            bvh::build(&self.shapes, &self.triangles, &self.csgs)
+                .unwrap_or_else(|stats| panic!("BVH is too deep for the shader's stack. {}", stats))
+        })
+    }
+
+    pub fn bvh_stats(&self) -> BvhStats {
+        BvhStats::of(self.get_bvh())
+    }
+
     pub fn view(&self) -> WorldView {
         WorldView {
             shapes: self.shapes.as_ptr(),
             lights: self.lights.as_ptr(),
             patterns: self.patterns.as_ptr(),
+            textures: self.textures.as_ptr(),
+            texels: self.texels.as_ptr(),
+            triangles: self.triangles.as_ptr(),
+            bvh: self.get_bvh().as_ptr(),
+            csgs: self.csgs.as_ptr(),
+            dropped_rays: self.dropped_rays.as_ptr(),
             __bindgen_padding_0: 0,
-            inputs: ShaderInputs {
-                camera: self.camera,
-                shape_count: self.shapes.len() as u32,
-                light_count: self.lights.len() as u32,
-            },
+            inputs: self.inputs(),
+        }
+    }
+
+    pub fn inputs(&self) -> ShaderInputs {
+        ShaderInputs {
+            camera: self.camera,
+            shape_count: self.shapes.len() as u32,
+            light_count: self.lights.len() as u32,
+            max_reflect_refract: self.settings.max_reflect_refract,
+            max_ray_queue: self.settings.max_ray_queue.clamp(1, MAX_RAY_QUEUE),
+            max_hits: self.settings.max_hits.clamp(1, MAX_HITS),
+            samples: self.settings.samples.clamp(1, MAX_SAMPLES),
+            sample_pattern: self.settings.sample_pattern,
+            integrator: self.settings.integrator,
+            frame_index: 0,
         }
     }
+
+    /// How many rays didn't fit in the queue since the last call. If it's not 0, settings.max_ray_queue is too small.
+    pub fn take_dropped_rays(&self) -> u32 {
+        self.dropped_rays.swap(0, Ordering::Relaxed)
+    }
+
+    /// For renderers that count dropped rays somewhere else (like a gpu buffer).
+    pub fn add_dropped_rays(&self, count: u32) {
+        self.dropped_rays.fetch_add(count, Ordering::Relaxed);
+    }
 }
 
 // Fight me clippy. There's no universe where (0.0..=1.0).contains(&x) is better.
 #[allow(clippy::manual_range_contains)]
-fn is_frac(x: f32) -> bool {
+pub(crate) fn is_frac(x: f32) -> bool {
     0.0 <= x && x <= 1.0
 }
 
-fn is_colour(c: Vec3A) -> bool {
-    is_frac(c.x) && is_frac(c.y) && is_frac(c.z)
-}
-
 impl Shape {
     pub fn set_transform(&mut self, mat: Mat4) {
         self.transform_inverse = mat.inverse();
@@ -121,10 +270,122 @@ impl Camera {
     }
 }
 
+impl Light {
+    pub fn point(position: Vec4, intensity: Vec3A) -> Light {
+        Light {
+            position,
+            intensity,
+            corner: position,
+            uvec: Vec4::ZERO,
+            vvec: Vec4::ZERO,
+            direction: Vec4::ZERO,
+            light_type: LightType::PointLight,
+            usteps: 1,
+            vsteps: 1,
+            cos_inner: 0.0,
+            cos_outer: 0.0,
+            attenuation_constant: 1.0,
+            attenuation_linear: 0.0,
+            attenuation_quadratic: 0.0,
+            jitter: false,
+            shadows: true,
+            from_shape: false,
+        }
+    }
+
+    /// Shines towards `direction` from infinitely far away.
+    pub fn directional(direction: Vec4, intensity: Vec3A) -> Light {
+        Light {
+            direction: direction.normalize(),
+            light_type: LightType::DirectionalLight,
+            ..Light::point(Vec4::ZERO, intensity)
+        }
+    }
+
+    /// The angles are in radians from `direction` to the edge of the cone (so half the width of the beam).
+    pub fn spot(position: Vec4, direction: Vec4, inner_angle: f32, outer_angle: f32, intensity: Vec3A) -> Light {
+        debug_assert!(inner_angle <= outer_angle);
+        Light {
+            direction: direction.normalize(),
+            light_type: LightType::SpotLight,
+            cos_inner: inner_angle.cos(),
+            cos_outer: outer_angle.cos(),
+            ..Light::point(position, intensity)
+        }
+    }
+
+    /// A rectangle with one corner at `corner` and sides `full_uvec` and `full_vvec`.
+    /// Each side is split into steps and there's a sample in the middle of each cell.
+    pub fn area(corner: Vec4, full_uvec: Vec4, usteps: u32, full_vvec: Vec4, vsteps: u32, intensity: Vec3A) -> Light {
+        debug_assert!(usteps <= MAX_LIGHT_STEPS && vsteps <= MAX_LIGHT_STEPS);
+        Light {
+            position: corner + full_uvec / 2.0 + full_vvec / 2.0,
+            uvec: full_uvec / usteps as f32,
+            vvec: full_vvec / vsteps as f32,
+            usteps,
+            vsteps,
+            ..Light::point(corner, intensity)
+        }
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.usteps * self.vsteps
+    }
+}
+
 impl Pattern {
     pub fn set_transform(&mut self, mat: Mat4) {
         self.transform_inverse = mat.inverse();
     }
+
+    /// `width` and `height` are how many squares fit across u and v.
+    pub fn uv_checkers(width: f32, height: f32, a: Vec3A, b: Vec3A) -> Pattern {
+        debug_assert!(width > 0.0 && height > 0.0);
+        Pattern {
+            a,
+            b,
+            width,
+            height,
+            ..PatternType::UvCheckers.create()
+        }
+    }
+
+    /// The corners are in the order of UvCorner.
+    pub fn align_check(main: Vec3A, corners: [Vec3A; 4]) -> Pattern {
+        Pattern {
+            a: main,
+            corners,
+            ..PatternType::UvAlignCheck.create()
+        }
+    }
+}
+
+impl TriangleData {
+    pub fn flat(p1: Vec4, p2: Vec4, p3: Vec4) -> TriangleData {
+        let e1 = p2 - p1;
+        let e2 = p3 - p1;
+        let normal = e2.truncate().cross(e1.truncate()).normalize().extend(0.0);
+        TriangleData {
+            p1,
+            p2,
+            p3,
+            e1,
+            e2,
+            normal,
+            n1: normal,
+            n2: normal,
+            n3: normal,
+        }
+    }
+
+    pub fn smooth(p1: Vec4, p2: Vec4, p3: Vec4, n1: Vec4, n2: Vec4, n3: Vec4) -> TriangleData {
+        TriangleData {
+            n1,
+            n2,
+            n3,
+            ..TriangleData::flat(p1, p2, p3)
+        }
+    }
 }
 
 
@@ -152,6 +413,7 @@ impl Default for Material {
     fn default() -> Self {
         Material {
             colour: Vec3A::new(1.0, 1.0, 1.0),
+            emissive: Vec3A::ZERO,
             pattern_index: -1,
             ambient: 0.1,
             diffuse: 0.9,
@@ -160,6 +422,9 @@ impl Default for Material {
             reflective: 0.0,
             transparency: 0.0,
             refractive_index: 1.0,
+            bump_index: -1,
+            bump: BumpMapping::HeightMap,
+            bump_amount: 1.0,
         }
     }
 }
@@ -172,6 +437,13 @@ impl ShapeType {
             index: 0,
             __bindgen_padding_0: 0,
             material: Default::default(),
+            minimum: -f32::MAX,
+            maximum: f32::MAX,
+            closed: false,
+            casts_shadow: true,
+            receives_shadow: true,
+            triangle_index: 0,
+            csg_index: 0,
         }
     }
 }
@@ -181,9 +453,24 @@ impl PatternType {
         Pattern {
             a: Default::default(),
             b: Default::default(),
-            transform_inverse: Mat4::IDENTITY,
-            __bindgen_padding_0: 0,
+            corners: Default::default(),
             pattern: self,
+            mapping: UvMapping::SphericalMap,
+            uv_pattern_index: -1,
+            texture_index: 0,
+            width: 1.0,
+            height: 1.0,
+            a_index: -1,
+            b_index: -1,
+            weight: 0.5,
+            // Marble and wood would just be smooth stripes and rings without it.
+            perturb: match self {
+                PatternType::Marble | PatternType::Wood => 1.0,
+                _ => 0.0,
+            },
+            noise_scale: 1.0,
+            octaves: 4,
+            transform_inverse: Mat4::IDENTITY,
         }
     }
 }
@@ -208,3 +495,31 @@ impl Clone for Pattern {
         *self
     }
 }
+
+impl Copy for Shape {}
+impl Clone for Shape {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Copy for Light {}
+impl Clone for Light {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Copy for BvhNode {}
+impl Clone for BvhNode {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl Copy for TriangleData {}
+impl Clone for TriangleData {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
@@ -3,7 +3,7 @@ use std::time::Instant;
 
 use crate::controller::CameraController;
 use crate::demo::*;
-use crate::shader_types::World;
+use crate::shader_types::{Integrator, SamplePattern, World, MAX_RAY_QUEUE, MAX_SAMPLES};
 use winit::dpi::LogicalSize;
 use winit::event::{DeviceEvent, ElementState, VirtualKeyCode};
 use winit::event_loop::EventLoop;
@@ -19,6 +19,8 @@ pub trait RenderStrategy: Sized + 'static {
     fn render(&mut self, app: &AppState);
     fn resized(&mut self, size: LogicalSize<u32>);
     fn world_changed(&mut self, app: &AppState);
+    /// The camera moved or the settings changed, so earlier frames don't look the same as the next one will.
+    fn view_changed(&mut self);
 
     fn run() {
         println!("Start.");
@@ -42,6 +44,7 @@ impl AppState {
             "Use the number keys to switch between included scenes. The window can be resized."
         );
         let world = initial_world();
+        println!("{}", world.bvh_stats());
         let event_loop = winit::event_loop::EventLoop::new();
         let size = LogicalSize::new(world.camera.size().0, world.camera.size().1);
 
@@ -73,11 +76,13 @@ impl AppState {
                         if input.state == ElementState::Pressed {
                             match input.virtual_keycode {
                                 Some(VirtualKeyCode::Escape) => *control_flow = ControlFlow::Exit,
+                                Some(key) if self.change_settings(key) => renderer.view_changed(),
                                 key => {
                                     if let Some(w) = preset_world(key) {
                                         println!("Switch scene."); // why tf am i at 10 levels of indentation
                                         let size = LogicalSize::new(w.camera.hsize, w.camera.vsize);
                                         self.window.set_inner_size(size);
+                                        println!("{}", w.bvh_stats());
                                         self.world = w;
                                         self.resize_camera();
                                         renderer.world_changed(&self);
@@ -103,11 +108,15 @@ impl AppState {
                     self.window.request_redraw();
                 }
                 Event::RedrawRequested(_) => {
-                    self.controller.update(
+                    let moved = self.controller.update(
                         &mut self.world.camera,
                         self.timer.last.elapsed().as_secs_f32(),
                     );
+                    if moved {
+                        renderer.view_changed();
+                    }
                     renderer.render(&self);
+                    self.timer.dropped_rays += self.world.take_dropped_rays();
                     self.timer.update();
                 }
                 _ => {}
@@ -115,6 +124,35 @@ impl AppState {
         });
     }
 
+    /// Returns false if the key isn't used for settings.
+    fn change_settings(&mut self, key: VirtualKeyCode) -> bool {
+        let settings = &mut self.world.settings;
+        match key {
+            VirtualKeyCode::LBracket => settings.max_reflect_refract = settings.max_reflect_refract.saturating_sub(1).max(1),
+            VirtualKeyCode::RBracket => settings.max_reflect_refract += 1,
+            VirtualKeyCode::Minus => settings.max_ray_queue = settings.max_ray_queue.saturating_sub(1).max(1),
+            VirtualKeyCode::Equals => settings.max_ray_queue = (settings.max_ray_queue + 1).min(MAX_RAY_QUEUE),
+            VirtualKeyCode::Comma => settings.samples = (settings.samples / 2).max(1),
+            VirtualKeyCode::Period => settings.samples = settings.samples.saturating_mul(2).min(MAX_SAMPLES),
+            VirtualKeyCode::Slash => {
+                settings.sample_pattern = match settings.sample_pattern {
+                    SamplePattern::SampleGrid => SamplePattern::SampleRotatedGrid,
+                    SamplePattern::SampleRotatedGrid => SamplePattern::SampleJittered,
+                    SamplePattern::SampleJittered => SamplePattern::SampleGrid,
+                }
+            }
+            VirtualKeyCode::P => {
+                settings.integrator = match settings.integrator {
+                    Integrator::WhittedTracing => Integrator::PathTracing,
+                    Integrator::PathTracing => Integrator::WhittedTracing,
+                }
+            }
+            _ => return false,
+        }
+        println!("{:?}", settings);
+        true
+    }
+
     fn resize_camera(&mut self) -> LogicalSize<u32> {
         let size: LogicalSize<u32> =
             LogicalSize::from_physical(self.window.inner_size(), self.window.scale_factor());
@@ -130,12 +168,14 @@ fn initial_world() -> World {
     let args: Vec<String> = env::args().collect();
     for name in args {
         if let Ok(data) = fs::read_to_string(&name) {
-            if let Ok(world) = load_scene(&data) {
-                return world;
+            match load_scene(&data) {
+                Ok(world) => return world,
+                Err(e) => println!("Failed to load {}: {}", name, e),
             }
         }
     }
 
+    println!("Using the first included scene.");
     load_scene(SCENE_FILES[0]).unwrap()
 }
 fn preset_world(key: Option<VirtualKeyCode>) -> Option<World> {
@@ -143,7 +183,10 @@ fn preset_world(key: Option<VirtualKeyCode>) -> Option<World> {
         let index = ((k as u32) - (VirtualKeyCode::Key1 as u32)) as usize;
         // Can't have more than 10 presets because the next key in the enum is 'A' which I want to use for movement.
         if index < SCENE_FILES.len() && index <= 10 {
-            return Some(load_scene(SCENE_FILES[index]).unwrap());
+            match load_scene(SCENE_FILES[index]) {
+                Ok(world) => return Some(world),
+                Err(e) => println!("Failed to load scene {}: {}", index + 1, e),
+            }
         }
     }
 
@@ -154,6 +197,7 @@ pub struct FrameTimer {
     pub frame_count: i32,
     pub micro_seconds: u128,
     pub last: Instant,
+    pub dropped_rays: u32,
 }
 
 impl FrameTimer {
@@ -162,6 +206,7 @@ impl FrameTimer {
             frame_count: 0,
             micro_seconds: 0,
             last: Instant::now(),
+            dropped_rays: 0,
         }
     }
 
@@ -187,7 +232,11 @@ impl FrameTimer {
             fps.round(),
             frame_time_ms
         );
+        if self.dropped_rays > 0 {
+            println!("{} rays didn't fit in the ray queue. Press = to make it bigger.", self.dropped_rays);
+        }
         self.micro_seconds = 0;
         self.frame_count = 0;
+        self.dropped_rays = 0;
     }
 }